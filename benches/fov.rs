@@ -0,0 +1,44 @@
+//! Compare the field of view algorithms on generated levels.
+//!
+//! Run with `cargo bench --bench fov`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use castle::logic::{Fov, Game};
+
+const SEEDS: u64 = 10;
+const RADIUS: i32 = 8;
+
+fn main() {
+    let games: Vec<Game> = (0..SEEDS).map(|seed| Game::new(seed).unwrap()).collect();
+    for (name, fov) in [("raycast", Fov::Raycast), ("shadowcast", Fov::Shadowcast)] {
+        let mut runs = 0;
+        let mut seen = 0;
+        let start = Instant::now();
+        for game in &games {
+            let level = game.level();
+            let (width, height) = (level.width() as i32, level.height() as i32);
+            let is_transparent =
+                |x: i32, y: i32| level.get_tile(x as usize, y as usize).is_transparent();
+            let mut visible = vec![false; level.width() * level.height()];
+            // look around from every open tile
+            for y in 0..height {
+                for x in 0..width {
+                    if !is_transparent(x, y) {
+                        continue;
+                    }
+                    fov.compute(&mut visible, width, height, is_transparent, (x, y), RADIUS);
+                    seen += black_box(&visible).iter().filter(|&&v| v).count();
+                    runs += 1;
+                }
+            }
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "{name:>10}: {runs} runs, {:.2} us per run, {:.1} tiles seen on average",
+            elapsed.as_secs_f64() * 1e6 / runs as f64,
+            seen as f64 / runs as f64,
+        );
+    }
+}
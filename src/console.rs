@@ -1,26 +1,86 @@
+use std::time::Duration;
 use std::{io, mem};
 
+pub use memory::Memory;
+
+mod memory;
 mod term;
 
+/// Output device for the console: a real terminal or an in-memory stand-in.
+pub trait Backend {
+    /// Show the `current` frame; `previous` is what is already on screen.
+    fn display(
+        &mut self,
+        current: &Buffer,
+        previous: &Buffer,
+        cursor: Option<(usize, usize)>,
+    ) -> Result<(), io::Error>;
+
+    fn read_event(&mut self) -> Result<Event, io::Error>;
+
+    /// Wait at most `timeout` for an event.
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, io::Error>;
+
+    /// Current size of the screen, in cells.
+    fn size(&self) -> Result<(usize, usize), io::Error>;
+
+    fn alert(&mut self) -> Result<(), io::Error>;
+
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error>;
+
+    /// The last frame shown and the cursor on it, for backends that keep them.
+    fn snapshot(&self) -> Option<(&Buffer, Option<(usize, usize)>)> {
+        None
+    }
+}
+
 pub struct Console {
-    terminal: term::Terminal,
+    backend: Box<dyn Backend>,
     front: Buffer,
     back: Buffer,
     cursor: Option<(usize, usize)>,
 }
 
 impl Console {
-    pub fn new(width: usize, height: usize, title: &str) -> Result<Self, io::Error> {
-        let mut terminal = term::Terminal::new()?;
-        terminal.set_title(title)?;
+    pub fn new(title: &str) -> Result<Self, io::Error> {
+        Self::with_backend(Box::new(term::Terminal::new()?), title)
+    }
+
+    pub fn with_backend(mut backend: Box<dyn Backend>, title: &str) -> Result<Self, io::Error> {
+        backend.set_title(title)?;
+        let (width, height) = backend.size()?;
         Ok(Console {
-            terminal,
+            backend,
             front: Buffer::new(width, height),
             back: Buffer::new(width, height),
             cursor: None,
         })
     }
 
+    pub fn width(&self) -> usize {
+        self.back.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.back.height
+    }
+
+    /// Current size of the screen, which may differ from that of the console.
+    pub fn screen_size(&self) -> Result<(usize, usize), io::Error> {
+        self.backend.size()
+    }
+
+    /// Adapt to a new screen size; the next frame will be redrawn in full.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.back = Buffer::new(width, height);
+        self.front = Buffer::new(width, height);
+        // make every cell differ from whatever the next frame contains
+        self.front
+            .cells
+            .fill(Cell::new('\0', Color::Default, Color::Default));
+        self.cursor = None;
+    }
+
     pub fn clear(&mut self) {
         self.back.clear();
         self.cursor = None;
@@ -58,15 +118,25 @@ impl Console {
 
     pub fn display(&mut self) -> Result<(), io::Error> {
         mem::swap(&mut self.back, &mut self.front);
-        self.terminal.display(&self.front, &self.back, self.cursor)
+        self.backend.display(&self.front, &self.back, self.cursor)
+    }
+
+    /// The last frame shown and the cursor on it, if the backend keeps them,
+    /// like [`Memory`] does.
+    pub fn snapshot(&self) -> Option<(&Buffer, Option<(usize, usize)>)> {
+        self.backend.snapshot()
     }
 
     pub fn alert(&mut self) -> Result<(), io::Error> {
-        self.terminal.alert()
+        self.backend.alert()
+    }
+
+    pub fn read_event(&mut self) -> Result<Event, io::Error> {
+        self.backend.read_event()
     }
 
-    pub fn read_event(&self) -> Result<Event, io::Error> {
-        self.terminal.read_event()
+    pub fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, io::Error> {
+        self.backend.poll_event(timeout)
     }
 }
 
@@ -92,11 +162,31 @@ pub enum Color {
     BrightMagenta,
     BrightCyan,
     BrightWhite,
+    // extended colors, downsampled when the terminal cannot show them
+    Indexed(u8),
+    Rgb(u8, u8, u8),
 }
 
 impl Color {
+    /// The color `amount`/255 of the way from this one to `other`.
+    ///
+    /// Only RGB colors can be mixed, anything else is left unchanged.
+    fn mix(self, other: Color, amount: u8) -> Self {
+        let (Color::Rgb(r0, g0, b0), Color::Rgb(r1, g1, b1)) = (self, other) else {
+            return self;
+        };
+        let mix = |a: u8, b: u8| (a as i32 + (b as i32 - a as i32) * amount as i32 / 255) as u8;
+        Color::Rgb(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+    }
+
     fn to_dim(self) -> Self {
         match self {
+            Color::Rgb(r, g, b) => Color::Rgb(r / 2, g / 2, b / 2),
+            Color::Indexed(index) if index >= 16 => {
+                let (r, g, b) = indexed_to_rgb(index);
+                Color::Rgb(r / 2, g / 2, b / 2)
+            }
+            Color::Indexed(index) => Color::Indexed(index).to_basic().to_dim(),
             // light/dark pairs
             Color::BrightRed => Color::Red,
             Color::BrightGreen => Color::Green,
@@ -111,6 +201,45 @@ impl Color {
             _ => self,
         }
     }
+
+    /// The basic or bright color for the first 16 palette indices.
+    fn to_basic(self) -> Self {
+        const BASIC: [Color; 16] = [
+            Color::Black,
+            Color::Red,
+            Color::Green,
+            Color::Yellow,
+            Color::Blue,
+            Color::Magenta,
+            Color::Cyan,
+            Color::White,
+            Color::BrightBlack,
+            Color::BrightRed,
+            Color::BrightGreen,
+            Color::BrightYellow,
+            Color::BrightBlue,
+            Color::BrightMagenta,
+            Color::BrightCyan,
+            Color::BrightWhite,
+        ];
+        match self {
+            Color::Indexed(index) if index < 16 => BASIC[index as usize],
+            _ => self,
+        }
+    }
+}
+
+/// Components of an entry of the standard 256-color palette, above the first 16.
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    debug_assert!(index >= 16);
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    if index >= 232 {
+        let gray = 8 + 10 * (index - 232);
+        (gray, gray, gray)
+    } else {
+        let n = (index - 16) as usize;
+        (LEVELS[n / 36], LEVELS[n / 6 % 6], LEVELS[n % 6])
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -124,6 +253,14 @@ impl Cell {
     pub fn new(ch: char, fg: Color, bg: Color) -> Cell {
         Cell { ch, fg, bg }
     }
+
+    /// This cell with its foreground `amount`/255 of the way to that of `other`.
+    pub fn shade_towards(self, other: Cell, amount: u8) -> Cell {
+        Cell {
+            fg: self.fg.mix(other.fg, amount),
+            ..self
+        }
+    }
 }
 
 impl Default for Cell {
@@ -136,7 +273,8 @@ impl Default for Cell {
     }
 }
 
-struct Buffer {
+#[derive(Clone)]
+pub struct Buffer {
     width: usize,
     height: usize,
     cells: Vec<Cell>,
@@ -155,7 +293,7 @@ impl Buffer {
         self.cells.fill(Cell::default());
     }
 
-    fn get(&self, x: usize, y: usize) -> Cell {
+    pub fn get(&self, x: usize, y: usize) -> Cell {
         debug_assert!(x < self.width && y < self.height);
         self.cells[y * self.width + x]
     }
@@ -165,6 +303,17 @@ impl Buffer {
         self.cells[y * self.width + x] = cell;
     }
 
+    /// The characters of each row, without trailing blanks.
+    /// The text of each row, without trailing spaces.
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.height)
+            .map(|y| {
+                let line: String = (0..self.width).map(|x| self.get(x, y).ch).collect();
+                line.trim_end().to_string()
+            })
+            .collect()
+    }
+
     fn fill_rect(&mut self, x0: usize, y0: usize, width: usize, height: usize, value: Cell) {
         debug_assert!(x0 + width <= self.width && y0 + height <= self.height);
         for y in y0..y0 + height {
@@ -180,13 +329,130 @@ impl Buffer {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Event {
     Abort,
+    Resize(usize, usize),
     KeyChar(char),
     KeySpecial(Key),
+    /// A key pressed while holding Ctrl or Alt, or a special key with Shift.
+    KeyModified(Modifiers, Press),
+    /// Pointer activity at a console position.
+    Mouse(Mouse, usize, usize),
+}
+
+impl Event {
+    /// Name of a key press, like `x`, `pgup` or `ctrl+x`.
+    pub fn key_name(&self) -> Option<String> {
+        let (modifiers, press) = match *self {
+            Event::KeyChar(ch) => (Modifiers::default(), Press::Char(ch)),
+            Event::KeySpecial(key) => (Modifiers::default(), Press::Special(key)),
+            Event::KeyModified(modifiers, press) => (modifiers, press),
+            _ => return None,
+        };
+        let mut name = String::new();
+        for (held, prefix) in [
+            (modifiers.ctrl, "ctrl+"),
+            (modifiers.alt, "alt+"),
+            (modifiers.shift, "shift+"),
+        ] {
+            if held {
+                name.push_str(prefix);
+            }
+        }
+        match press {
+            Press::Char(' ') => name.push_str("space"),
+            Press::Char(ch) => name.push(ch),
+            Press::Special(key) => name.push_str(key.name()),
+        }
+        Some(name)
+    }
+
+    /// The key press with the given name, the reverse of `key_name`.
+    pub fn from_key_name(name: &str) -> Option<Event> {
+        let mut modifiers = Modifiers::default();
+        let mut rest = name;
+        loop {
+            if let Some(tail) = rest.strip_prefix("ctrl+") {
+                modifiers.ctrl = true;
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix("alt+") {
+                modifiers.alt = true;
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix("shift+") {
+                modifiers.shift = true;
+                rest = tail;
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let press = match (chars.next(), chars.next()) {
+            (Some(ch), None) => Press::Char(ch),
+            _ if rest == "space" => Press::Char(' '),
+            _ => Press::Special(Key::from_name(rest)?),
+        };
+        let event = match press {
+            // characters already include the effect of Shift
+            Press::Char(ch) if !modifiers.ctrl && !modifiers.alt => Event::KeyChar(ch),
+            Press::Special(key) if modifiers == Modifiers::default() => Event::KeySpecial(key),
+            Press::Char(_) => Event::KeyModified(
+                Modifiers {
+                    shift: false,
+                    ..modifiers
+                },
+                press,
+            ),
+            Press::Special(_) => Event::KeyModified(modifiers, press),
+        };
+        Some(event)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mouse {
+    Move,
+    Click,
+    WheelUp,
+    WheelDown,
 }
 
+impl Mouse {
+    const ALL: [Mouse; 4] = [Mouse::Move, Mouse::Click, Mouse::WheelUp, Mouse::WheelDown];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mouse::Move => "move",
+            Mouse::Click => "click",
+            Mouse::WheelUp => "wheel-up",
+            Mouse::WheelDown => "wheel-down",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mouse> {
+        Mouse::ALL.into_iter().find(|mouse| mouse.name() == name)
+    }
+}
+
+/// Modifier keys held during a key press.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Press {
+    Char(char),
+    Special(Key),
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum Key {
+    Enter,
+    Escape,
+    Backspace,
     Left,
     Right,
     Up,
@@ -196,3 +462,39 @@ pub enum Key {
     PgUp,
     PgDn,
 }
+
+impl Key {
+    const ALL: [Key; 11] = [
+        Key::Enter,
+        Key::Escape,
+        Key::Backspace,
+        Key::Left,
+        Key::Right,
+        Key::Up,
+        Key::Down,
+        Key::Home,
+        Key::End,
+        Key::PgUp,
+        Key::PgDn,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Key::Enter => "enter",
+            Key::Escape => "escape",
+            Key::Backspace => "backspace",
+            Key::Left => "left",
+            Key::Right => "right",
+            Key::Up => "up",
+            Key::Down => "down",
+            Key::Home => "home",
+            Key::End => "end",
+            Key::PgUp => "pgup",
+            Key::PgDn => "pgdn",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Key> {
+        Key::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
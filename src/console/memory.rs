@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+use super::{Backend, Buffer, Event};
+
+/// Backend without a terminal, for tests and headless runs.
+///
+/// Input comes from a script of events; the last frame and its cursor are
+/// kept for [`super::Console::snapshot`].
+pub struct Memory {
+    width: usize,
+    height: usize,
+    events: VecDeque<Event>,
+    frame: Buffer,
+    cursor: Option<(usize, usize)>,
+}
+
+impl Memory {
+    pub fn new(width: usize, height: usize, events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            width,
+            height,
+            events: events.into_iter().collect(),
+            frame: Buffer::new(0, 0),
+            cursor: None,
+        }
+    }
+}
+
+impl Backend for Memory {
+    fn display(
+        &mut self,
+        current: &Buffer,
+        previous: &Buffer,
+        cursor: Option<(usize, usize)>,
+    ) -> Result<(), io::Error> {
+        debug_assert!(current.width == previous.width && current.height == previous.height);
+        debug_assert!(cursor.is_none_or(|(x, y)| x < current.width && y < current.height));
+        self.frame = current.clone();
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    /// Once the script runs out, the player walks away.
+    fn read_event(&mut self) -> Result<Event, io::Error> {
+        Ok(self.events.pop_front().unwrap_or(Event::Abort))
+    }
+
+    /// The script never makes the player wait.
+    fn poll_event(&mut self, _timeout: Duration) -> Result<Option<Event>, io::Error> {
+        Ok(self.events.pop_front())
+    }
+
+    fn size(&self) -> Result<(usize, usize), io::Error> {
+        Ok((self.width, self.height))
+    }
+
+    fn alert(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    fn set_title(&mut self, _title: &str) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Option<(&Buffer, Option<(usize, usize)>)> {
+        Some((&self.frame, self.cursor))
+    }
+}
@@ -1,31 +1,67 @@
+use std::env;
 use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
 
 use crossterm::{
     cursor::{self, MoveTo},
-    event::{KeyCode, KeyEventKind, KeyModifiers},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEventKind, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
     execute, queue,
     style::{self, Print, SetBackgroundColor, SetForegroundColor},
     terminal::{self, ClearType::All, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
 };
 
-use super::{Buffer, Event, Key};
+use super::{Backend, Buffer, Event, Key, Modifiers, Mouse, Press, indexed_to_rgb};
 
 use super::Color as ApiColor;
 use crossterm::style::Color as TermColor;
 
 pub struct Terminal {
     stdout: Stdout,
+    depth: ColorDepth,
     _screen: AltScreen,
 }
 
 impl Terminal {
     pub fn new() -> Result<Self, io::Error> {
         let stdout = io::stdout();
+        let depth = ColorDepth::detect();
         let _screen = AltScreen::enter()?;
-        Ok(Self { stdout, _screen })
+        Ok(Self {
+            stdout,
+            depth,
+            _screen,
+        })
     }
+}
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq)]
+enum ColorDepth {
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Guess from the environment, as there is no reliable way to query the terminal.
+    fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Indexed256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
 
-    pub fn display(
+impl Backend for Terminal {
+    fn display(
         &mut self,
         current: &Buffer,
         previous: &Buffer,
@@ -50,11 +86,17 @@ impl Terminal {
                         queue!(self.stdout, MoveTo(x as u16, y as u16))?;
                     }
                     if curr.fg != last_fg {
-                        queue!(self.stdout, SetForegroundColor(convert_color(curr.fg)))?;
+                        queue!(
+                            self.stdout,
+                            SetForegroundColor(convert_color(curr.fg, self.depth))
+                        )?;
                         last_fg = curr.fg;
                     }
                     if curr.bg != last_bg {
-                        queue!(self.stdout, SetBackgroundColor(convert_color(curr.bg)))?;
+                        queue!(
+                            self.stdout,
+                            SetBackgroundColor(convert_color(curr.bg, self.depth))
+                        )?;
                         last_bg = curr.bg;
                     }
                     queue!(self.stdout, Print(curr.ch))?;
@@ -69,45 +111,113 @@ impl Terminal {
         self.stdout.flush()
     }
 
-    pub fn set_title(&mut self, title: &str) -> Result<(), io::Error> {
+    fn size(&self) -> Result<(usize, usize), io::Error> {
+        let (width, height) = terminal::size()?;
+        Ok((width as usize, height as usize))
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), io::Error> {
         execute!(self.stdout, SetTitle(title))
     }
 
-    pub fn alert(&mut self) -> Result<(), io::Error> {
+    fn alert(&mut self) -> Result<(), io::Error> {
         execute!(self.stdout, Print('\x07'))
     }
 
-    pub fn read_event(&self) -> Result<Event, io::Error> {
+    fn read_event(&mut self) -> Result<Event, io::Error> {
         loop {
-            if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
-                if key.kind == KeyEventKind::Release {
-                    continue;
-                }
-                if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL {
-                    return Ok(Event::Abort);
-                }
-                if key.modifiers == KeyModifiers::NONE {
-                    let e = match key.code {
-                        KeyCode::Char(ch) => Event::KeyChar(ch),
-                        KeyCode::Left => Event::KeySpecial(Key::Left),
-                        KeyCode::Right => Event::KeySpecial(Key::Right),
-                        KeyCode::Up => Event::KeySpecial(Key::Up),
-                        KeyCode::Down => Event::KeySpecial(Key::Down),
-                        KeyCode::Home => Event::KeySpecial(Key::Home),
-                        KeyCode::End => Event::KeySpecial(Key::End),
-                        KeyCode::PageUp => Event::KeySpecial(Key::PgUp),
-                        KeyCode::PageDown => Event::KeySpecial(Key::PgDn),
-                        _ => continue,
-                    };
-                    return Ok(e);
-                }
+            if let Some(event) = convert_event(crossterm::event::read()?) {
+                return Ok(event);
             }
         }
     }
+
+    fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, io::Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if !crossterm::event::poll(left)? {
+                return Ok(None);
+            }
+            if let Some(event) = convert_event(crossterm::event::read()?) {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+/// The event for a terminal event, if it is one the game cares about.
+fn convert_event(event: crossterm::event::Event) -> Option<Event> {
+    if let crossterm::event::Event::Resize(width, height) = event {
+        return Some(Event::Resize(width as usize, height as usize));
+    }
+    if let crossterm::event::Event::Mouse(mouse) = event {
+        let kind = match mouse.kind {
+            MouseEventKind::Moved | MouseEventKind::Drag(_) => Mouse::Move,
+            MouseEventKind::Down(MouseButton::Left) => Mouse::Click,
+            MouseEventKind::ScrollUp => Mouse::WheelUp,
+            MouseEventKind::ScrollDown => Mouse::WheelDown,
+            _ => return None,
+        };
+        return Some(Event::Mouse(
+            kind,
+            mouse.column as usize,
+            mouse.row as usize,
+        ));
+    }
+    if let crossterm::event::Event::Key(key) = event {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL {
+            return Some(Event::Abort);
+        }
+        let press = match key.code {
+            KeyCode::Char(ch) => Press::Char(ch),
+            KeyCode::Enter => Press::Special(Key::Enter),
+            KeyCode::Esc => Press::Special(Key::Escape),
+            KeyCode::Backspace => Press::Special(Key::Backspace),
+            KeyCode::Left => Press::Special(Key::Left),
+            KeyCode::Right => Press::Special(Key::Right),
+            KeyCode::Up => Press::Special(Key::Up),
+            KeyCode::Down => Press::Special(Key::Down),
+            KeyCode::Home => Press::Special(Key::Home),
+            KeyCode::End => Press::Special(Key::End),
+            KeyCode::PageUp => Press::Special(Key::PgUp),
+            KeyCode::PageDown => Press::Special(Key::PgDn),
+            _ => return None,
+        };
+        let modifiers = Modifiers {
+            ctrl: key.modifiers.contains(KeyModifiers::CONTROL),
+            alt: key.modifiers.contains(KeyModifiers::ALT),
+            // the character is already shifted
+            shift: key.modifiers.contains(KeyModifiers::SHIFT) && !matches!(press, Press::Char(_)),
+        };
+        let e = match press {
+            _ if modifiers != Modifiers::default() => Event::KeyModified(modifiers, press),
+            Press::Char(ch) => Event::KeyChar(ch),
+            Press::Special(key) => Event::KeySpecial(key),
+        };
+        return Some(e);
+    }
+    None
 }
 
-fn convert_color(color: ApiColor) -> TermColor {
+fn convert_color(color: ApiColor, depth: ColorDepth) -> TermColor {
     match color {
+        ApiColor::Rgb(r, g, b) => match depth {
+            ColorDepth::TrueColor => TermColor::Rgb { r, g, b },
+            ColorDepth::Indexed256 => TermColor::AnsiValue(nearest_indexed(r, g, b)),
+            ColorDepth::Ansi16 => convert_color(nearest_basic(r, g, b), depth),
+        },
+        ApiColor::Indexed(index) if index < 16 => convert_color(color.to_basic(), depth),
+        ApiColor::Indexed(index) => match depth {
+            ColorDepth::Ansi16 => {
+                let (r, g, b) = indexed_to_rgb(index);
+                convert_color(nearest_basic(r, g, b), depth)
+            }
+            _ => TermColor::AnsiValue(index),
+        },
         ApiColor::Default => TermColor::Reset,
         ApiColor::Black => TermColor::Black,
         ApiColor::Red => TermColor::DarkRed,
@@ -128,20 +238,84 @@ fn convert_color(color: ApiColor) -> TermColor {
     }
 }
 
+/// Closest entry of the 6x6x6 color cube or of the grayscale ramp.
+fn nearest_indexed(r: u8, g: u8, b: u8) -> u8 {
+    // the cube levels are 0, 95, 135, 175, 215 and 255
+    let level = |c: u8| match c {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (c - 35) / 40,
+    };
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    let average = (r as u16 + g as u16 + b as u16) / 3;
+    // the ramp runs from 8 to 238 in steps of 10, black and white are in the cube
+    let gray = match average {
+        0..4 => 16,
+        4..247 => 232 + ((average - 3) / 10).min(23) as u8,
+        _ => 231,
+    };
+    if distance((r, g, b), indexed_to_rgb(gray)) < distance((r, g, b), indexed_to_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Closest basic or bright color, assuming the usual xterm palette.
+fn nearest_basic(r: u8, g: u8, b: u8) -> ApiColor {
+    const PALETTE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let index = (0..PALETTE.len())
+        .min_by_key(|&i| distance((r, g, b), PALETTE[i]))
+        .unwrap();
+    ApiColor::Indexed(index as u8).to_basic()
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
 /// Ensure that the terminal is reset when this struct is dropped.
 struct AltScreen;
 
 impl AltScreen {
     pub fn enter() -> Result<Self, io::Error> {
         terminal::enable_raw_mode()?;
-        execute!(io::stdout(), cursor::Hide, EnterAlternateScreen)?;
+        execute!(
+            io::stdout(),
+            cursor::Hide,
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
         execute!(io::stdout(), style::ResetColor, terminal::Clear(All))?;
         Ok(Self)
     }
 
     fn leave() -> Result<(), io::Error> {
         execute!(io::stdout(), style::ResetColor, terminal::Clear(All))?;
-        execute!(io::stdout(), LeaveAlternateScreen, cursor::Show)?;
+        execute!(
+            io::stdout(),
+            DisableMouseCapture,
+            LeaveAlternateScreen,
+            cursor::Show
+        )?;
         terminal::disable_raw_mode()
     }
 }
@@ -151,3 +325,54 @@ impl Drop for AltScreen {
         Self::leave().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_colors_map_to_themselves() {
+        for index in 16..=255 {
+            let (r, g, b) = indexed_to_rgb(index);
+            assert_eq!(nearest_indexed(r, g, b), index);
+        }
+    }
+
+    #[test]
+    fn nearest_cube_level() {
+        // either side of halfway between levels 0 and 95, and 95 and 135,
+        // on saturated colors that are nowhere near the gray ramp
+        assert_eq!(nearest_indexed(47, 255, 255), 16 + 6 * 5 + 5);
+        assert_eq!(nearest_indexed(48, 255, 255), 16 + 36 + 6 * 5 + 5);
+        assert_eq!(nearest_indexed(255, 114, 0), 16 + 36 * 5 + 6);
+        assert_eq!(nearest_indexed(255, 116, 0), 16 + 36 * 5 + 6 * 2);
+        assert_eq!(nearest_indexed(250, 130, 10), 16 + 36 * 5 + 6 * 2);
+    }
+
+    #[test]
+    fn nearest_gray_step() {
+        assert_eq!(nearest_indexed(3, 3, 3), 16);
+        assert_eq!(nearest_indexed(12, 12, 12), 232);
+        assert_eq!(nearest_indexed(13, 13, 13), 233);
+        assert_eq!(nearest_indexed(100, 101, 102), 241);
+        assert_eq!(nearest_indexed(243, 243, 243), 255);
+        assert_eq!(nearest_indexed(250, 250, 250), 231);
+    }
+
+    #[test]
+    fn nearest_basic_color() {
+        let cases = [
+            ((0, 0, 0), ApiColor::Black),
+            ((190, 10, 10), ApiColor::Red),
+            ((250, 20, 20), ApiColor::BrightRed),
+            ((10, 0, 230), ApiColor::Blue),
+            ((100, 100, 250), ApiColor::BrightBlue),
+            ((120, 130, 125), ApiColor::BrightBlack),
+            ((220, 220, 220), ApiColor::White),
+            ((255, 250, 255), ApiColor::BrightWhite),
+        ];
+        for ((r, g, b), expected) in cases {
+            assert!(nearest_basic(r, g, b) == expected, "{r}, {g}, {b}");
+        }
+    }
+}
@@ -1,31 +1,193 @@
 use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use console::{Console, Event};
+use console::{Console, Event, Memory};
 use logic::Game;
-use ui::{Scene, Transition, scenes};
+use rand::Rng;
+use replay::{Recorder, Replay};
+use ui::scenes::{self, TooSmallScreen};
+use ui::{Scene, Transition};
 
 mod console;
-mod logic;
+pub mod logic;
+mod replay;
+#[cfg(test)]
+mod testing;
 mod ui;
 
-pub fn run() -> Result<(), io::Error> {
-    let mut console = Console::new(ui::CONSOLE_WIDTH, ui::CONSOLE_HEIGHT, "Goblin Castle")?;
-    let mut game = Game::new();
+const SAVE_FILE: &str = "castle.sav";
+
+/// Parse a dungeon seed, either in decimal or in hexadecimal with a `0x` prefix.
+pub fn parse_seed(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[derive(Default)]
+pub struct Options {
+    /// Seed of the dungeon, random if not given.
+    pub seed: Option<u64>,
+    /// Record the seed, the key map and all input events to this file.
+    pub record: Option<PathBuf>,
+    /// Play back the input events recorded in this file.
+    pub replay: Option<PathBuf>,
+    /// Pause between replayed events.
+    pub replay_delay: Duration,
+    /// Wait for a key press before each replayed event.
+    pub replay_step: bool,
+    /// Run without a terminal and print the final screen to standard output.
+    pub headless: bool,
+    /// Name of a built-in theme or path to a theme file.
+    pub theme: Option<String>,
+    /// Name of a built-in key map or path to a key map file; replays use
+    /// the key map they were recorded with instead.
+    pub keymap: Option<String>,
+    /// Directory of vault files to use instead of the built-in vaults.
+    pub vaults: Option<PathBuf>,
+}
+
+pub fn run(options: Options) -> Result<(), io::Error> {
+    let replay = options.replay.as_deref().map(Replay::open).transpose()?;
+    let seed = match &replay {
+        Some(replay) => replay.seed(),
+        None => options.seed.unwrap_or_else(|| rand::rng().random()),
+    };
+    if let Some(theme) = &options.theme {
+        ui::theme::select(theme)?;
+    }
+    // the same keys must mean the same commands as when recording
+    match (&replay, &options.keymap) {
+        (Some(replay), _) => ui::keymap::select_recorded(replay.keymap())?,
+        (None, Some(keymap)) => ui::keymap::select(keymap)?,
+        (None, None) => {}
+    }
+    if let Some(dir) = &options.vaults {
+        logic::select_vaults(dir)?;
+    }
+    let recorder = match &options.record {
+        Some(path) => Some(Recorder::create(path, seed, &ui::keymap::to_text())?),
+        None => None,
+    };
+    // saved games would make recordings impossible to reproduce
+    let save_file = (recorder.is_none() && replay.is_none()).then_some(Path::new(SAVE_FILE));
+
+    let title = "Goblin Castle";
+    let mut console = if options.headless {
+        let backend = Memory::new(ui::MIN_CONSOLE_WIDTH, ui::MIN_CONSOLE_HEIGHT, []);
+        Console::with_backend(Box::new(backend), title)?
+    } else {
+        Console::new(title)?
+    };
+    let mut game = Game::new(seed).map_err(io::Error::other)?;
+    let result = play(
+        &mut console,
+        &mut game,
+        replay,
+        recorder,
+        save_file,
+        &options,
+    );
+    // keep the run however it ended, even when the terminal went away
+    let saved = match save_file {
+        // only keep games that have actually been played
+        Some(path) if game.turn() > 0 && !game.is_over() => game.save(path),
+        _ => Ok(()),
+    };
+    result.and(saved)?;
+
+    if options.headless
+        && let Some((frame, _)) = console.snapshot()
+    {
+        for line in frame.lines() {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// Run scenes until the player leaves or the console fails.
+fn play(
+    console: &mut Console,
+    game: &mut Game,
+    mut replay: Option<Replay>,
+    mut recorder: Option<Recorder>,
+    save_file: Option<&Path>,
+    options: &Options,
+) -> Result<(), io::Error> {
     let mut stack: Vec<Box<dyn Scene>> = vec![];
-    let mut scene: Box<dyn Scene> = Box::new(scenes::StartScreen);
+    let mut scene: Box<dyn Scene> = Box::new(scenes::StartScreen::new(save_file));
+    if let Some(recorder) = &mut recorder {
+        // mouse events are only meaningful at the size they were made at
+        recorder.record(&Event::Resize(console.width(), console.height()))?;
+    }
 
     loop {
+        let fits = TooSmallScreen::fits(console);
         console.clear();
-        for scene in &stack {
-            scene.render(&game, &mut console);
+        if fits {
+            for scene in &stack {
+                scene.render(game, console);
+            }
+            scene.render(game, console);
+        } else {
+            TooSmallScreen.render(game, console);
         }
-        scene.render(&game, &mut console);
         console.display()?;
 
-        let event = console.read_event()?;
+        let event = match replay.as_mut() {
+            Some(replay) => {
+                let input = if options.headless {
+                    None
+                } else if options.replay_step {
+                    Some(console.read_event()?)
+                } else {
+                    // keep an eye out for Ctrl+C, which raw mode swallows
+                    console.poll_event(options.replay_delay)?
+                };
+                match input {
+                    Some(Event::Abort) => Some(Event::Abort),
+                    // the console keeps the size of the recording, so that
+                    // clicks land on the same tiles; only redraw it
+                    Some(Event::Resize(..)) => {
+                        Some(Event::Resize(console.width(), console.height()))
+                    }
+                    _ => replay.next_event(),
+                }
+            }
+            _ => Some(console.read_event()?),
+        };
+        let Some(event) = event else {
+            // the recording is over, hand control back to the player
+            replay = None;
+            if !options.headless {
+                let (width, height) = console.screen_size()?;
+                console.resize(width, height);
+            }
+            continue;
+        };
+        // nothing but resizing and leaving works while the console is too small
+        let ignored = !fits && !matches!(event, Event::Abort | Event::Resize(..));
+        if !ignored && let Some(recorder) = &mut recorder {
+            recorder.record(&event)?;
+        }
+        if let Event::Resize(width, height) = event {
+            console.resize(width, height);
+            continue;
+        }
+        if ignored {
+            // the pointer may just be passing by
+            if !matches!(event, Event::Mouse(..)) {
+                console.alert()?;
+            }
+            continue;
+        }
         match event {
             Event::Abort => break,
-            event => match scene.handle_event(&mut game, event) {
+            event => match scene.handle_event(game, event) {
                 Transition::Okay => {}
                 Transition::Beep => console.alert()?,
                 Transition::Switch(next) => scene = next,
@@ -40,9 +202,9 @@ pub fn run() -> Result<(), io::Error> {
                         break;
                     }
                 }
+                Transition::Quit => break,
             },
         }
     }
-
     Ok(())
 }
@@ -1,64 +1,683 @@
-use level::Level;
-use messages::MessageLog;
+//! Game rules and state, independent of any terminal.
+//!
+//! A [`Game`] is fully determined by its seed, the vaults in use and the
+//! sequence of [`Action`]s performed on it, which makes it suitable for tests
+//! and bots.
 
+use std::path::Path;
+use std::{io, mem};
+
+pub use ai::{Ai, Behavior};
+pub use combat::Fighter;
+pub use fov::Fov;
+pub use generate::{GenerationError, select_vaults};
+pub use items::{Inventory, Item};
+pub use level::{FULL_LIGHT, Level, RoomKind};
+pub use messages::MessageLog;
+use save::{Persist, Reader, Writer, corrupt};
+
+mod ai;
+mod combat;
 mod fov;
 mod generate;
+mod items;
 mod level;
 mod messages;
+mod path;
+mod save;
+
+/// How far away a lightning bolt can strike, in tiles.
+const LIGHTNING_RANGE: usize = 5;
+/// How far the thunder of a lightning bolt carries, in tiles.
+const LIGHTNING_NOISE: i32 = 12;
+/// How far the light of a wall torch reaches, in tiles.
+const TORCH_LIGHT: usize = 6;
+/// How far the glow of lava reaches, in tiles.
+const LAVA_LIGHT: usize = 3;
+/// Hit points lost on each turn spent wading through lava.
+const LAVA_DAMAGE: i32 = 8;
+/// How far the crash of a door being forced open carries, in tiles.
+const FORCE_NOISE: i32 = 10;
+/// How far the player's lantern shines while it has oil, in tiles.
+const LANTERN_LIGHT: usize = 5;
+/// Turns a full lantern burns for.
+const LANTERN_FUEL: u32 = 1000;
+/// Turns of burning added by a flask of oil.
+const FLASK_FUEL: u32 = 500;
+/// Turns left when the player is warned that the lantern is running out.
+const LANTERN_LOW: u32 = 100;
+
+/// Something the player can do on their turn.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Step in a direction, attacking any monster in the way; `(0, 0)` waits.
+    Move(i8, i8),
+    Descend,
+    Ascend,
+    PickUp,
+    /// Use the inventory item with the given index.
+    Use(usize),
+    /// Use the inventory item with the given index on a position, for items
+    /// that need a target.
+    UseOn(usize, (usize, usize)),
+    /// Drop the inventory item with the given index.
+    Drop(usize),
+}
+
+/// Why an action could not be performed; no turn passes when one fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionError {
+    /// The player is dead.
+    GameOver,
+    /// A wall or the edge of the map is in the way, or the stairs are blocked.
+    Blocked,
+    /// There are no stairs here going that way.
+    NoStairs,
+    /// There is nothing to pick up here, or no item with that index.
+    NoItem,
+    PackFull,
+    /// The item would be wasted, such as a potion at full health.
+    NoEffect,
+    /// The item needs a target, and there is no monster in reach there.
+    NoTarget,
+}
 
 pub struct Game {
+    seed: u64,
     level: Level,
+    /// Previously visited levels, the nearest one last.
+    upper_levels: Vec<Level>,
+    lower_levels: Vec<Level>,
+    inventory: Inventory,
     log: MessageLog,
+    fov: Fov,
+    /// Turns left before the player's lantern goes out.
+    lantern: u32,
 }
 
 impl Game {
-    pub fn new() -> Self {
-        let mut level = generate::generate_level();
+    /// Start a new game, or fail if no playable first level can be made from the seed.
+    pub fn new(seed: u64) -> Result<Self, GenerationError> {
+        let mut level = generate::generate_level(seed, 1)?;
         let (x, y) = level.entry();
-        let player = Entity::new(x, y, Glyph::Player);
+        let mut player = Entity::new(x, y, Glyph::Player, Fighter::new(30, 5, 2), 8, None);
+        player.light = LANTERN_LIGHT;
         level.add_player(player);
-        level.update_vision();
+        level.update_vision(Fov::Shadowcast);
 
         let mut log = MessageLog::new(100);
         log.append("Welcome to the Dungeon!");
 
-        Game { level, log }
+        Ok(Game {
+            seed,
+            level,
+            upper_levels: Vec::new(),
+            lower_levels: Vec::new(),
+            inventory: Inventory::new(26),
+            log,
+            fov: Fov::Shadowcast,
+            lantern: LANTERN_FUEL,
+        })
+    }
+
+    /// Change the field of view algorithm, which takes effect immediately.
+    pub fn set_fov(&mut self, fov: Fov) {
+        self.fov = fov;
+        self.level.update_vision(fov);
+    }
+
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        save::read_file(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        save::write_file(path, self)
+    }
+
+    /// Perform one player action, followed by the monsters' turns.
+    ///
+    /// Fails without using up a turn if the action is not possible.
+    pub fn perform(&mut self, action: Action) -> Result<(), ActionError> {
+        if self.is_over() {
+            return Err(ActionError::GameOver);
+        }
+        match action {
+            Action::Move(dx, dy) => self.move_player(dx, dy),
+            Action::Descend => self.descend(),
+            Action::Ascend => self.ascend(),
+            Action::PickUp => self.pick_up(),
+            Action::Use(index) => self.use_item(index, None),
+            Action::UseOn(index, target) => self.use_item(index, Some(target)),
+            Action::Drop(index) => self.drop_item(index),
+        }?;
+        self.burn_player();
+        self.burn_lantern();
+        // glowing monsters may have moved, and the lantern may have gone out
+        self.level.update_vision(self.fov);
+        Ok(())
     }
 
-    pub fn move_player(&mut self, dx: i8, dy: i8) -> Result<(), ()> {
+    /// Hurt the player for standing on a damaging tile, such as lava.
+    fn burn_player(&mut self) {
+        let tile = self.player_tile();
+        let damage = tile.properties().damage;
+        if damage <= 0 || self.is_over() {
+            return;
+        }
+        self.log.append(format!(
+            "{} burns you for {damage} hit points!",
+            messages::capitalize(tile.name())
+        ));
+        combat::strike(self.level.player_mut().unwrap(), damage, &mut self.log);
+    }
+
+    fn burn_lantern(&mut self) {
+        if self.lantern == 0 {
+            return;
+        }
+        self.lantern -= 1;
+        match self.lantern {
+            0 => {
+                self.level.player_mut().unwrap().light = 0;
+                self.log.append("Your lantern goes out!");
+            }
+            LANTERN_LOW => self.log.append("Your lantern is running low on oil."),
+            _ => {}
+        }
+    }
+
+    fn move_player(&mut self, dx: i8, dy: i8) -> Result<(), ActionError> {
         let player = self.level.player().unwrap();
         let x = player.x() as i32 + dx as i32;
         let y = player.y() as i32 + dy as i32;
         if x >= 0 && y >= 0 {
             let x = x as usize;
             let y = y as usize;
-            if x < self.level.width()
-                && y < self.level.height()
-                && self.level.get_tile(x, y) == Tile::Floor
-            {
+            if let Some(index) = self.level.actor_at(x, y) {
+                self.log.start_turn();
+                self.player_attack(index);
+                self.monsters_turn();
+                return Ok(());
+            }
+            if x >= self.level.width() || y >= self.level.height() {
+                return Err(ActionError::Blocked);
+            }
+            let tile = self.level.get_tile(x, y);
+            if let Some(opened) = tile.opened() {
+                self.log.start_turn();
+                self.level.set_tile(x, y, opened);
+                if tile == Tile::DoorLocked {
+                    self.log
+                        .append("You force the locked door open with a crash!");
+                    ai::hear_noise(&mut self.level, (x, y), FORCE_NOISE, &mut self.log);
+                }
+                self.level.update_vision(self.fov);
+                self.monsters_turn();
+                return Ok(());
+            }
+            if tile.is_walkable() {
                 self.log.start_turn();
                 let player = self.level.player_mut().unwrap();
                 player.set_pos(x, y);
-                self.level.update_vision();
+                self.level.update_vision(self.fov);
+                if let Some(item) = self.level.item_at(x, y) {
+                    self.log.append(format!("You see a {} here.", item.name()));
+                }
+                // waiting in place makes no sound
+                if (dx, dy) != (0, 0) {
+                    ai::hear_noise(&mut self.level, (x, y), ai::STEP_NOISE, &mut self.log);
+                }
+                self.monsters_turn();
                 return Ok(());
             }
         }
-        Err(())
+        Err(ActionError::Blocked)
+    }
+
+    fn pick_up(&mut self) -> Result<(), ActionError> {
+        let (x, y) = self.level.player().unwrap().pos();
+        let item = self.level.item_at(x, y).ok_or(ActionError::NoItem)?;
+        if self.inventory.is_full() {
+            self.log.append("Your pack is full.");
+            return Err(ActionError::PackFull);
+        }
+        self.log.start_turn();
+        self.level.take_item(x, y);
+        self.inventory.add(item);
+        self.log.append(format!("You pick up the {}.", item.name()));
+        self.monsters_turn();
+        Ok(())
+    }
+
+    fn drop_item(&mut self, index: usize) -> Result<(), ActionError> {
+        if index >= self.inventory.items().len() {
+            return Err(ActionError::NoItem);
+        }
+        self.log.start_turn();
+        let item = self.inventory.remove(index);
+        let (x, y) = self.level.player().unwrap().pos();
+        self.level.add_item(x, y, item);
+        self.log.append(format!("You drop the {}.", item.name()));
+        self.monsters_turn();
+        Ok(())
+    }
+
+    fn use_item(
+        &mut self,
+        index: usize,
+        target: Option<(usize, usize)>,
+    ) -> Result<(), ActionError> {
+        if index >= self.inventory.items().len() {
+            return Err(ActionError::NoItem);
+        }
+        let item = self.inventory.items()[index];
+        match item {
+            Item::HealingPotion => {
+                let player = self.level.player_mut().unwrap();
+                if player.fighter.hp() == player.fighter.max_hp() {
+                    self.log.append("You are already at full health.");
+                    return Err(ActionError::NoEffect);
+                }
+                self.log.start_turn();
+                let healed = player.fighter.heal(10);
+                self.log
+                    .append(format!("You feel better, recovering {healed} hit points."));
+            }
+            Item::LightningScroll => {
+                let target = target.and_then(|pos| self.visible_monster_at(pos, LIGHTNING_RANGE));
+                let Some(index) = target else {
+                    self.log
+                        .append("There is no enemy close enough to strike there.");
+                    return Err(ActionError::NoTarget);
+                };
+                self.log.start_turn();
+                let target = self.level.actor_mut(index);
+                self.log.append(format!(
+                    "A lightning bolt strikes {} for 20 hit points!",
+                    target.glyph.name()
+                ));
+                combat::strike(target, 20, &mut self.log);
+                let pos = target.pos();
+                if target.fighter.is_dead() {
+                    self.level.remove_actor(index);
+                }
+                let tile = self.level.get_tile(pos.0, pos.1);
+                if tile.properties().flammable {
+                    self.level.set_tile(pos.0, pos.1, Tile::Floor);
+                    self.log.append(format!(
+                        "{} goes up in flames.",
+                        messages::capitalize(tile.name())
+                    ));
+                }
+                ai::hear_noise(&mut self.level, pos, LIGHTNING_NOISE, &mut self.log);
+            }
+            Item::MappingScroll => {
+                self.log.start_turn();
+                self.level.reveal_map();
+                self.log
+                    .append("The layout of this floor is revealed to you.");
+            }
+            Item::OilFlask => {
+                if self.lantern >= LANTERN_FUEL {
+                    self.log.append("Your lantern is already full.");
+                    return Err(ActionError::NoEffect);
+                }
+                self.log.start_turn();
+                self.lantern = (self.lantern + FLASK_FUEL).min(LANTERN_FUEL);
+                self.level.player_mut().unwrap().light = LANTERN_LIGHT;
+                self.log.append("You refill your lantern.");
+            }
+        }
+        self.inventory.remove(index);
+        self.monsters_turn();
+        Ok(())
+    }
+
+    /// The monster at `(x, y)`, if the player can see it within `range` tiles.
+    fn visible_monster_at(&self, (x, y): (usize, usize), range: usize) -> Option<usize> {
+        let (px, py) = self.level.player().unwrap().pos();
+        let distance = x.abs_diff(px).max(y.abs_diff(py));
+        if distance > range || !self.level.is_visible(x, y) {
+            return None;
+        }
+        self.level.actor_at(x, y)
+    }
+
+    fn descend(&mut self) -> Result<(), ActionError> {
+        if self.player_tile() != Tile::DownStairs {
+            return Err(ActionError::NoStairs);
+        }
+        let next = match self.lower_levels.pop() {
+            Some(level) => level,
+            None => match generate::generate_level(self.seed, self.depth() + 1) {
+                Ok(level) => level,
+                Err(e) => {
+                    self.log.append("The stairs are blocked by rubble.");
+                    self.log.append(format!("({e})"));
+                    return Err(ActionError::Blocked);
+                }
+            },
+        };
+        let player = self.level.take_player().unwrap();
+        let prev = mem::replace(&mut self.level, next);
+        self.upper_levels.push(prev);
+        let (x, y) = self.level.entry();
+        self.enter_level(player, x, y);
+        self.log.append("You descend deeper into the castle.");
+        Ok(())
+    }
+
+    fn ascend(&mut self) -> Result<(), ActionError> {
+        if self.player_tile() != Tile::UpStairs {
+            return Err(ActionError::NoStairs);
+        }
+        let next = self.upper_levels.pop().ok_or(ActionError::NoStairs)?;
+        let player = self.level.take_player().unwrap();
+        let prev = mem::replace(&mut self.level, next);
+        self.lower_levels.push(prev);
+        let (x, y) = self.level.exit().unwrap();
+        self.enter_level(player, x, y);
+        self.log.append("You climb back up the stairs.");
+        Ok(())
+    }
+
+    fn enter_level(&mut self, mut player: Entity, x: usize, y: usize) {
+        // shove aside any monster standing on the stairs
+        if let Some(index) = self.level.actor_at(x, y)
+            && let Some((nx, ny)) = path::nearest_free(&self.level, (x, y))
+        {
+            self.level.actor_mut(index).set_pos(nx, ny);
+        }
+        self.log.start_turn();
+        player.set_pos(x, y);
+        self.level.add_player(player);
+        self.level.update_vision(self.fov);
+    }
+
+    fn player_tile(&self) -> Tile {
+        let (x, y) = self.level.player().unwrap().pos();
+        self.level.get_tile(x, y)
+    }
+
+    fn player_attack(&mut self, index: usize) {
+        let (player, target) = self.level.player_and_actor_mut(index);
+        combat::attack(player, target, &mut self.log);
+        let pos = target.pos();
+        if target.fighter.is_dead() {
+            self.level.remove_actor(index);
+        }
+        ai::hear_noise(&mut self.level, pos, ai::COMBAT_NOISE, &mut self.log);
+    }
+
+    fn monsters_turn(&mut self) {
+        // what the player has in sight is what can see them, so one field of
+        // view serves every monster until one of them opens a door
+        let radius = self
+            .level
+            .actors()
+            .iter()
+            .map(|e| e.sight)
+            .max()
+            .unwrap_or(0);
+        let mut in_sight = self.level.player_line_of_sight(radius, self.fov);
+        for index in 0..self.level.actors().len() {
+            if ai::take_turn(&mut self.level, index, &in_sight, &mut self.log) {
+                in_sight = self.level.player_line_of_sight(radius, self.fov);
+            }
+            if self.is_over() {
+                break;
+            }
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.level.player().unwrap().fighter.is_dead()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.log.turn()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.upper_levels.len() + 1
     }
 
     pub fn level(&self) -> &Level {
         &self.level
     }
 
+    /// Steps from the player to an explored tile, over explored tiles only.
+    pub fn path_to(&self, x: usize, y: usize) -> Option<Vec<(usize, usize)>> {
+        if x >= self.level.width() || y >= self.level.height() || !self.level.is_explored(x, y) {
+            return None;
+        }
+        let from = self.level.player().unwrap().pos();
+        path::find_known_path(&self.level, from, (x, y))
+    }
+
+    /// Turns left before the player's lantern goes out.
+    pub fn lantern(&self) -> u32 {
+        self.lantern
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
     pub fn log(&self) -> &MessageLog {
         &self.log
     }
 }
 
+#[cfg(test)]
+impl Game {
+    /// Go straight down the stairs, for tests that need a deeper level.
+    pub fn take_stairs_down(&mut self) {
+        let (x, y) = self.level.exit().unwrap();
+        self.level.player_mut().unwrap().set_pos(x, y);
+        self.perform(Action::Descend).unwrap();
+    }
+}
+
+impl Persist for Game {
+    fn save(&self, w: &mut Writer) {
+        w.u64(self.seed);
+        self.level.save(w);
+        w.seq(self.upper_levels.iter());
+        w.seq(self.lower_levels.iter());
+        self.inventory.save(w);
+        self.log.save(w);
+        self.fov.save(w);
+        w.u32(self.lantern);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let game = Game {
+            seed: r.u64()?,
+            level: Level::load(r)?,
+            upper_levels: r.seq()?,
+            lower_levels: r.seq()?,
+            inventory: Inventory::load(r)?,
+            log: MessageLog::load(r)?,
+            fov: Fov::load(r)?,
+            lantern: r.u32()?,
+        };
+        if game.level.player().is_none() {
+            return Err(corrupt("missing player"));
+        }
+        let others = game.upper_levels.iter().chain(&game.lower_levels);
+        if others.into_iter().any(|level| level.player().is_some()) {
+            return Err(corrupt("player on more than one level"));
+        }
+        Ok(game)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum Tile {
     Wall,
     Floor,
+    DownStairs,
+    UpStairs,
+    /// A wall with a burning torch on it.
+    Torch,
+    DoorOpen,
+    /// Opens when walked into.
+    DoorClosed,
+    /// Can only be forced open, and not quietly.
+    DoorLocked,
+    /// Deep enough to swim in, which monsters will not do.
+    Water,
+    Lava,
+    /// Blocks the way but not the view.
+    Chasm,
+}
+
+/// What a kind of tile is like, for movement, sight and light alike.
+pub struct TileProperties {
+    pub walkable: bool,
+    pub transparent: bool,
+    /// Hit points lost on every turn spent on the tile.
+    pub damage: i32,
+    pub swimmable: bool,
+    /// Whether a lightning bolt burns it away.
+    pub flammable: bool,
+    /// How far the light of the tile reaches, in tiles; 0 if it gives none.
+    pub light: usize,
+    pub name: &'static str,
+}
+
+impl Tile {
+    pub fn properties(self) -> &'static TileProperties {
+        const SOLID: TileProperties = TileProperties {
+            walkable: false,
+            transparent: false,
+            damage: 0,
+            swimmable: false,
+            flammable: false,
+            light: 0,
+            name: "a wall",
+        };
+        const OPEN: TileProperties = TileProperties {
+            walkable: true,
+            transparent: true,
+            ..SOLID
+        };
+        match self {
+            Tile::Wall => &SOLID,
+            Tile::Floor => &TileProperties {
+                name: "the floor",
+                ..OPEN
+            },
+            Tile::DownStairs => &TileProperties {
+                name: "stairs leading down",
+                ..OPEN
+            },
+            Tile::UpStairs => &TileProperties {
+                name: "stairs leading up",
+                ..OPEN
+            },
+            Tile::Torch => &TileProperties {
+                light: TORCH_LIGHT,
+                name: "a torch on the wall",
+                ..SOLID
+            },
+            Tile::DoorOpen => &TileProperties {
+                flammable: true,
+                name: "an open door",
+                ..OPEN
+            },
+            Tile::DoorClosed => &TileProperties {
+                flammable: true,
+                name: "a closed door",
+                ..SOLID
+            },
+            Tile::DoorLocked => &TileProperties {
+                name: "a locked door",
+                ..SOLID
+            },
+            Tile::Water => &TileProperties {
+                swimmable: true,
+                name: "deep water",
+                ..OPEN
+            },
+            Tile::Lava => &TileProperties {
+                damage: LAVA_DAMAGE,
+                light: LAVA_LIGHT,
+                name: "lava",
+                ..OPEN
+            },
+            Tile::Chasm => &TileProperties {
+                walkable: false,
+                name: "a chasm",
+                ..OPEN
+            },
+        }
+    }
+
+    pub fn is_walkable(self) -> bool {
+        self.properties().walkable
+    }
+
+    pub fn is_transparent(self) -> bool {
+        self.properties().transparent
+    }
+
+    /// How far the light of this tile reaches, in tiles; 0 if it gives none.
+    pub fn light(self) -> usize {
+        self.properties().light
+    }
+
+    pub fn name(self) -> &'static str {
+        self.properties().name
+    }
+
+    /// The tile a door becomes once opened, if this is a door that is shut.
+    pub fn opened(self) -> Option<Tile> {
+        match self {
+            Tile::DoorClosed | Tile::DoorLocked => Some(Tile::DoorOpen),
+            _ => None,
+        }
+    }
+}
+
+impl Persist for Tile {
+    fn save(&self, w: &mut Writer) {
+        w.u8(match self {
+            Tile::Wall => 0,
+            Tile::Floor => 1,
+            Tile::DownStairs => 2,
+            Tile::UpStairs => 3,
+            Tile::Torch => 4,
+            Tile::DoorOpen => 5,
+            Tile::DoorClosed => 6,
+            Tile::DoorLocked => 7,
+            Tile::Water => 8,
+            Tile::Lava => 9,
+            Tile::Chasm => 10,
+        });
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        match r.u8()? {
+            0 => Ok(Tile::Wall),
+            1 => Ok(Tile::Floor),
+            2 => Ok(Tile::DownStairs),
+            3 => Ok(Tile::UpStairs),
+            4 => Ok(Tile::Torch),
+            5 => Ok(Tile::DoorOpen),
+            6 => Ok(Tile::DoorClosed),
+            7 => Ok(Tile::DoorLocked),
+            8 => Ok(Tile::Water),
+            9 => Ok(Tile::Lava),
+            10 => Ok(Tile::Chasm),
+            _ => Err(corrupt("unknown tile")),
+        }
+    }
 }
 
 pub enum Glyph {
@@ -67,18 +686,45 @@ pub enum Glyph {
     Hobgobin,
 }
 
+impl Glyph {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Glyph::Player => "you",
+            Glyph::Goblin => "the goblin",
+            Glyph::Hobgobin => "the hobgoblin",
+        }
+    }
+}
+
 pub struct Entity {
-    x: u8,
-    y: u8,
+    x: u16,
+    y: u16,
     pub glyph: Glyph,
+    pub fighter: Fighter,
+    /// How far this entity can see, in tiles.
+    pub sight: usize,
+    /// How far the light this entity carries reaches, in tiles; 0 if none.
+    pub light: usize,
+    pub ai: Option<Ai>,
 }
 
 impl Entity {
-    pub fn new(x: usize, y: usize, glyph: Glyph) -> Self {
+    pub fn new(
+        x: usize,
+        y: usize,
+        glyph: Glyph,
+        fighter: Fighter,
+        sight: usize,
+        ai: Option<Ai>,
+    ) -> Self {
         Entity {
-            x: x as u8,
-            y: y as u8,
+            x: x as u16,
+            y: y as u16,
             glyph,
+            fighter,
+            sight,
+            light: 0,
+            ai,
         }
     }
 
@@ -95,7 +741,40 @@ impl Entity {
     }
 
     pub fn set_pos(&mut self, x: usize, y: usize) {
-        self.x = x as u8;
-        self.y = y as u8;
+        self.x = x as u16;
+        self.y = y as u16;
+    }
+}
+
+impl Persist for Entity {
+    fn save(&self, w: &mut Writer) {
+        w.u16(self.x);
+        w.u16(self.y);
+        w.u8(match self.glyph {
+            Glyph::Player => 0,
+            Glyph::Goblin => 1,
+            Glyph::Hobgobin => 2,
+        });
+        self.fighter.save(w);
+        w.u8(self.sight as u8);
+        w.u8(self.light as u8);
+        self.ai.save(w);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        Ok(Entity {
+            x: r.u16()?,
+            y: r.u16()?,
+            glyph: match r.u8()? {
+                0 => Glyph::Player,
+                1 => Glyph::Goblin,
+                2 => Glyph::Hobgobin,
+                _ => return Err(corrupt("unknown glyph")),
+            },
+            fighter: Fighter::load(r)?,
+            sight: r.u8()? as usize,
+            light: r.u8()? as usize,
+            ai: Option::load(r)?,
+        })
     }
 }
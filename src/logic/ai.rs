@@ -0,0 +1,281 @@
+use std::io;
+
+use super::messages::{MessageLog, capitalize};
+use super::path::{self, DijkstraMap};
+use super::save::{Persist, Reader, Writer, corrupt};
+use super::{combat, level::Level};
+
+/// How far the noise of a footstep carries, in tiles.
+pub const STEP_NOISE: i32 = 3;
+/// How far the noise of a fight carries, in tiles.
+pub const COMBAT_NOISE: i32 = 8;
+
+/// How a monster reacts to the player; this is what sets goblins apart from hobgoblins.
+#[derive(Clone, Copy)]
+pub enum Behavior {
+    /// Charges on sight, runs away when badly hurt and forgets the player when out of sight.
+    Cowardly,
+    /// Never retreats and keeps tracking the last known position of the player.
+    Relentless,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum State {
+    /// Unaware of anything until woken up by a noise.
+    Asleep,
+    Idle,
+    Hunting {
+        target: (usize, usize),
+    },
+    /// Going to check a noise heard at `target`, until there.
+    Investigating {
+        target: (usize, usize),
+    },
+    Fleeing,
+}
+
+#[derive(Clone, Copy)]
+pub struct Ai {
+    behavior: Behavior,
+    state: State,
+}
+
+impl Ai {
+    pub fn new(behavior: Behavior) -> Self {
+        Ai {
+            behavior,
+            state: State::Idle,
+        }
+    }
+
+    pub fn asleep(behavior: Behavior) -> Self {
+        Ai {
+            behavior,
+            state: State::Asleep,
+        }
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.state == State::Asleep
+    }
+}
+
+impl Persist for Ai {
+    fn save(&self, w: &mut Writer) {
+        w.u8(match self.behavior {
+            Behavior::Cowardly => 0,
+            Behavior::Relentless => 1,
+        });
+        match self.state {
+            State::Idle => w.u8(0),
+            State::Hunting { target } => {
+                w.u8(1);
+                w.usize(target.0);
+                w.usize(target.1);
+            }
+            State::Fleeing => w.u8(2),
+            State::Asleep => w.u8(3),
+            State::Investigating { target } => {
+                w.u8(4);
+                w.usize(target.0);
+                w.usize(target.1);
+            }
+        }
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let behavior = match r.u8()? {
+            0 => Behavior::Cowardly,
+            1 => Behavior::Relentless,
+            _ => return Err(corrupt("unknown monster behavior")),
+        };
+        let state = match r.u8()? {
+            0 => State::Idle,
+            1 => State::Hunting {
+                target: (r.usize()?, r.usize()?),
+            },
+            2 => State::Fleeing,
+            3 => State::Asleep,
+            4 => State::Investigating {
+                target: (r.usize()?, r.usize()?),
+            },
+            _ => return Err(corrupt("unknown monster state")),
+        };
+        Ok(Ai { behavior, state })
+    }
+}
+
+/// Let the actor at `index` take its turn.
+///
+/// `in_sight` holds the tiles in the player's line of sight, from
+/// [`Level::player_line_of_sight`], out to at least the actor's sight.
+/// Returns whether the actor opened a door, which may change them.
+pub fn take_turn(level: &mut Level, index: usize, in_sight: &[bool], log: &mut MessageLog) -> bool {
+    let monster = &level.actors()[index];
+    let Some(mut ai) = monster.ai else {
+        return false;
+    };
+    if ai.is_asleep() {
+        return false;
+    }
+    let pos = monster.pos();
+    let fighter = monster.fighter;
+    let player = level.player().unwrap().pos();
+    let (dx, dy) = (pos.0.abs_diff(player.0), pos.1.abs_diff(player.1));
+    let sees_player = dx * dx + dy * dy <= monster.sight * monster.sight
+        && in_sight[pos.1 * level.width() + pos.0];
+
+    let next = match (ai.behavior, sees_player, ai.state) {
+        (Behavior::Cowardly, true, _) if fighter.hp() * 2 <= fighter.max_hp() => State::Fleeing,
+        (_, true, _) => State::Hunting { target: player },
+        (_, false, State::Investigating { target }) if target != pos => ai.state,
+        (Behavior::Relentless, false, State::Hunting { target }) if target != pos => ai.state,
+        (_, false, _) => State::Idle,
+    };
+    if next == State::Fleeing && ai.state != State::Fleeing {
+        log.append(format!(
+            "{} flees!",
+            capitalize(level.actors()[index].glyph.name())
+        ));
+    }
+    ai.state = next;
+    level.actor_mut(index).ai = Some(ai);
+
+    match ai.state {
+        State::Asleep | State::Idle => false,
+        State::Hunting { target } | State::Investigating { target } => {
+            if target == player && is_adjacent(pos, player) {
+                attack_player(level, index, log);
+                false
+            } else if let Some(next) = step_towards(level, pos, target) {
+                step(level, index, next)
+            } else {
+                false
+            }
+        }
+        State::Fleeing => {
+            if let Some(next) = step_away(level, pos, player) {
+                step(level, index, next)
+            } else {
+                if is_adjacent(pos, player) {
+                    // cornered
+                    attack_player(level, index, log);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Move the actor at `index` onto `next`, or open the door there instead.
+///
+/// Returns whether a door was opened.
+fn step(level: &mut Level, index: usize, (x, y): (usize, usize)) -> bool {
+    match level.get_tile(x, y).opened() {
+        Some(opened) => {
+            level.set_tile(x, y, opened);
+            true
+        }
+        None => {
+            level.actor_mut(index).set_pos(x, y);
+            false
+        }
+    }
+}
+
+fn attack_player(level: &mut Level, index: usize, log: &mut MessageLog) {
+    let (player, monster) = level.player_and_actor_mut(index);
+    combat::attack(monster, player, log);
+    let pos = player.pos();
+    hear_noise(level, pos, COMBAT_NOISE, log);
+}
+
+/// Wake up and alert the monsters within earshot of a noise made at `source`.
+///
+/// Sound travels along walkable tiles, so walls muffle it, and fades by one
+/// tile per step. Monsters that are not busy go and check where it came from.
+pub fn hear_noise(level: &mut Level, source: (usize, usize), volume: i32, log: &mut MessageLog) {
+    let distances = DijkstraMap::within(level, &[source], volume);
+    for index in 0..level.actors().len() {
+        let monster = &level.actors()[index];
+        let Some(mut ai) = monster.ai else {
+            continue;
+        };
+        let (x, y) = monster.pos();
+        if distances
+            .get(x, y)
+            .is_none_or(|distance| distance >= volume)
+        {
+            continue;
+        }
+        if ai.is_asleep() && level.is_visible(x, y) {
+            log.append(format!("{} wakes up!", capitalize(monster.glyph.name())));
+        }
+        if matches!(ai.state, State::Asleep | State::Idle) {
+            ai.state = State::Investigating { target: source };
+        }
+        level.actor_mut(index).ai = Some(ai);
+    }
+}
+
+fn is_adjacent(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0.abs_diff(b.0) <= 1 && a.1.abs_diff(b.1) <= 1
+}
+
+fn step_towards(level: &Level, from: (usize, usize), to: (usize, usize)) -> Option<(usize, usize)> {
+    let next = *path::find_path(level, from, to)?.first()?;
+    (!level.is_occupied(next.0, next.1)).then_some(next)
+}
+
+fn step_away(
+    level: &Level,
+    from: (usize, usize),
+    threat: (usize, usize),
+) -> Option<(usize, usize)> {
+    DijkstraMap::new(level, &[threat])
+        .flee(level)
+        .downhill(level, from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::{Entity, Fighter, Glyph, Tile};
+
+    /// A corridor with a cowardly goblin at its west end and the player,
+    /// out of its sight, at the east end.
+    fn corridor() -> Level {
+        let mut level = Level::new(9, 3, (7, 1));
+        for x in 1..8 {
+            level.set_tile(x, 1, Tile::Floor);
+        }
+        let player = Entity::new(7, 1, Glyph::Player, Fighter::new(30, 5, 2), 8, None);
+        level.add_player(player);
+        let ai = Some(Ai::new(Behavior::Cowardly));
+        level.add_actor(Entity::new(
+            1,
+            1,
+            Glyph::Goblin,
+            Fighter::new(10, 3, 0),
+            6,
+            ai,
+        ));
+        level
+    }
+
+    #[test]
+    fn noise_is_checked_out_until_reached() {
+        let mut level = corridor();
+        let mut log = MessageLog::new(10);
+        let in_sight = vec![false; level.width() * level.height()];
+        hear_noise(&mut level, (4, 1), COMBAT_NOISE, &mut log);
+        for x in 2..=4 {
+            take_turn(&mut level, 0, &in_sight, &mut log);
+            assert_eq!(level.actors()[0].pos(), (x, 1));
+        }
+        // nothing there, so back to idling
+        take_turn(&mut level, 0, &in_sight, &mut log);
+        assert_eq!(level.actors()[0].pos(), (4, 1));
+        assert!(level.actors()[0].ai.unwrap().state == State::Idle);
+    }
+}
@@ -0,0 +1,121 @@
+use std::io;
+
+use super::Entity;
+use super::messages::{MessageLog, capitalize};
+use super::save::{Persist, Reader, Writer, corrupt};
+
+#[derive(Clone, Copy)]
+pub struct Fighter {
+    max_hp: i32,
+    hp: i32,
+    power: i32,
+    defense: i32,
+}
+
+impl Fighter {
+    pub fn new(max_hp: i32, power: i32, defense: i32) -> Self {
+        Fighter {
+            max_hp,
+            hp: max_hp,
+            power,
+            defense,
+        }
+    }
+
+    pub fn hp(&self) -> i32 {
+        self.hp
+    }
+
+    pub fn max_hp(&self) -> i32 {
+        self.max_hp
+    }
+
+    pub fn power(&self) -> i32 {
+        self.power
+    }
+
+    pub fn defense(&self) -> i32 {
+        self.defense
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0
+    }
+
+    pub fn take_damage(&mut self, amount: i32) {
+        self.hp = (self.hp - amount).max(0);
+    }
+
+    /// Restore up to `amount` hit points and return how many were actually restored.
+    pub fn heal(&mut self, amount: i32) -> i32 {
+        let healed = amount.min(self.max_hp - self.hp);
+        self.hp += healed;
+        healed
+    }
+}
+
+impl Persist for Fighter {
+    fn save(&self, w: &mut Writer) {
+        w.i32(self.max_hp);
+        w.i32(self.hp);
+        w.i32(self.power);
+        w.i32(self.defense);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let fighter = Fighter {
+            max_hp: r.i32()?,
+            hp: r.i32()?,
+            power: r.i32()?,
+            defense: r.i32()?,
+        };
+        if fighter.hp > fighter.max_hp {
+            return Err(corrupt("hit points out of range"));
+        }
+        Ok(fighter)
+    }
+}
+
+/// Resolve one melee attack and report the outcome in the message log.
+pub fn attack(attacker: &Entity, defender: &mut Entity, log: &mut MessageLog) {
+    let damage = attacker.fighter.power() - defender.fighter.defense();
+    let subject = attacker.glyph.name();
+    let object = defender.glyph.name();
+    let hit = conjugate(subject, "hit", "hits");
+    let msg = if damage > 0 {
+        defender.fighter.take_damage(damage);
+        let points = if damage == 1 {
+            "hit point"
+        } else {
+            "hit points"
+        };
+        format!("{subject} {hit} {object} for {damage} {points}.")
+    } else {
+        let does = conjugate(subject, "do", "does");
+        format!("{subject} {hit} {object} but {does} no damage.")
+    };
+    log.append(capitalize(&msg));
+    report_death(defender, log);
+}
+
+/// Deal damage that ignores defense, such as from magic.
+pub fn strike(defender: &mut Entity, damage: i32, log: &mut MessageLog) {
+    defender.fighter.take_damage(damage);
+    report_death(defender, log);
+}
+
+fn report_death(defender: &Entity, log: &mut MessageLog) {
+    if defender.fighter.is_dead() {
+        let object = defender.glyph.name();
+        let die = conjugate(object, "die", "dies");
+        log.append(capitalize(&format!("{object} {die}!")));
+    }
+}
+
+fn conjugate<'a>(subject: &str, second_person: &'a str, third_person: &'a str) -> &'a str {
+    if subject == "you" {
+        second_person
+    } else {
+        third_person
+    }
+}
@@ -1,18 +1,82 @@
+use std::io;
+
+use super::save::{Persist, Reader, Writer, corrupt};
+
+/// Field of view algorithm.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fov {
+    /// Bresenham rays to every cell in range; simple but asymmetric.
+    Raycast,
+    /// Symmetric recursive shadowcasting: if A sees B, B sees A.
+    Shadowcast,
+}
+
+impl Fov {
+    /// Mark the cells seen from `origin` within `radius`.
+    ///
+    /// Opaque cells are visible themselves but hide what is behind them.
+    pub fn compute<F>(
+        self,
+        visible: &mut [bool],
+        width: i32,
+        height: i32,
+        is_transparent: F,
+        origin: (i32, i32),
+        radius: i32,
+    ) where
+        F: Fn(i32, i32) -> bool,
+    {
+        visible.fill(false);
+        match self {
+            Fov::Raycast => raycast(visible, width, height, is_transparent, origin, radius),
+            Fov::Shadowcast => {
+                let (x, y) = origin;
+                visible[y as usize * width as usize + x as usize] = true;
+                for quadrant in Quadrant::ALL {
+                    let mut caster = Shadowcaster {
+                        visible: &mut *visible,
+                        width,
+                        height,
+                        is_transparent: &is_transparent,
+                        origin,
+                        radius,
+                        quadrant,
+                    };
+                    caster.scan(1, (-1, 1), (1, 1));
+                }
+            }
+        }
+    }
+}
+
+impl Persist for Fov {
+    fn save(&self, w: &mut Writer) {
+        w.u8(match self {
+            Fov::Raycast => 0,
+            Fov::Shadowcast => 1,
+        });
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        match r.u8()? {
+            0 => Ok(Fov::Raycast),
+            1 => Ok(Fov::Shadowcast),
+            _ => Err(corrupt("unknown field of view")),
+        }
+    }
+}
+
 /// line-of-sight FOV algorithm
-pub fn compute_fov<F>(
+fn raycast<F>(
     visible: &mut [bool],
     width: i32,
     height: i32,
     is_transparent: F,
-    player_x: i32,
-    player_y: i32,
+    (player_x, player_y): (i32, i32),
+    radius: i32,
 ) where
     F: Fn(i32, i32) -> bool,
 {
-    let radius = 8;
-
-    visible.fill(false);
-
     for dy in -radius..=radius {
         for dx in -radius..=radius {
             // skip if outside the circle radius
@@ -64,3 +128,149 @@ fn line(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
     }
     points
 }
+
+/// One of the four 90 degree cones around the origin, by its central direction.
+#[derive(Clone, Copy)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    const ALL: [Quadrant; 4] = [
+        Quadrant::North,
+        Quadrant::East,
+        Quadrant::South,
+        Quadrant::West,
+    ];
+
+    /// Offset from the origin of the cell at `depth` rows out and `col` columns across.
+    fn transform(self, depth: i32, col: i32) -> (i32, i32) {
+        match self {
+            Quadrant::North => (col, -depth),
+            Quadrant::East => (depth, col),
+            Quadrant::South => (col, depth),
+            Quadrant::West => (-depth, col),
+        }
+    }
+}
+
+/// Shadowcasting over one quadrant, after Albert Ford's "Symmetric Shadowcasting".
+///
+/// Slopes are kept as exact fractions `(numerator, denominator)` with a
+/// positive denominator, so that the result does not depend on rounding.
+struct Shadowcaster<'a, F> {
+    visible: &'a mut [bool],
+    width: i32,
+    height: i32,
+    is_transparent: &'a F,
+    origin: (i32, i32),
+    radius: i32,
+    quadrant: Quadrant,
+}
+
+impl<F> Shadowcaster<'_, F>
+where
+    F: Fn(i32, i32) -> bool,
+{
+    /// Scan the row at `depth` between two slopes, then the rows behind it.
+    fn scan(&mut self, depth: i32, mut start: (i32, i32), end: (i32, i32)) {
+        if depth > self.radius {
+            return;
+        }
+        // columns whose centers fall within the slopes, ties rounded inwards
+        let min_col = (2 * depth * start.0 + start.1).div_euclid(2 * start.1);
+        let max_col = -(-(2 * depth * end.0 - end.1)).div_euclid(2 * end.1);
+        let mut prev_wall = None;
+        for col in min_col..=max_col {
+            let wall = self.is_wall(depth, col);
+            let symmetric = col * start.1 >= depth * start.0 && col * end.1 <= depth * end.0;
+            if wall || symmetric {
+                self.reveal(depth, col);
+            }
+            // left edge of a cell, as a slope
+            let slope = (2 * col - 1, 2 * depth);
+            if prev_wall == Some(true) && !wall {
+                start = slope;
+            }
+            if prev_wall == Some(false) && wall {
+                self.scan(depth + 1, start, slope);
+            }
+            prev_wall = Some(wall);
+        }
+        if prev_wall == Some(false) {
+            self.scan(depth + 1, start, end);
+        }
+    }
+
+    fn cell(&self, depth: i32, col: i32) -> Option<(i32, i32)> {
+        let (dx, dy) = self.quadrant.transform(depth, col);
+        let (x, y) = (self.origin.0 + dx, self.origin.1 + dy);
+        (x >= 0 && x < self.width && y >= 0 && y < self.height).then_some((x, y))
+    }
+
+    /// Cells off the map block the view like walls.
+    fn is_wall(&self, depth: i32, col: i32) -> bool {
+        match self.cell(depth, col) {
+            Some((x, y)) => !(self.is_transparent)(x, y),
+            None => true,
+        }
+    }
+
+    fn reveal(&mut self, depth: i32, col: i32) {
+        if depth * depth + col * col > self.radius * self.radius {
+            return;
+        }
+        if let Some((x, y)) = self.cell(depth, col) {
+            self.visible[y as usize * self.width as usize + x as usize] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::generate::generate_level;
+
+    /// A little more than the player's lantern reaches.
+    const RADIUS: i32 = 8;
+
+    #[test]
+    fn shadowcasting_is_symmetric() {
+        let level = generate_level(7, 1).unwrap();
+        let (width, height) = (level.width() as i32, level.height() as i32);
+        let is_transparent =
+            |x: i32, y: i32| level.get_tile(x as usize, y as usize).is_transparent();
+        let open: Vec<(i32, i32)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| is_transparent(x, y))
+            .collect();
+        let views: Vec<Vec<bool>> = open
+            .iter()
+            .map(|&origin| {
+                let mut visible = vec![false; (width * height) as usize];
+                Fov::Shadowcast.compute(
+                    &mut visible,
+                    width,
+                    height,
+                    is_transparent,
+                    origin,
+                    RADIUS,
+                );
+                visible
+            })
+            .collect();
+        let sees = |view: &[bool], (x, y): (i32, i32)| view[(y * width + x) as usize];
+        for (&a, from_a) in open.iter().zip(&views) {
+            for (&b, from_b) in open.iter().zip(&views) {
+                let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+                if dx * dx + dy * dy > RADIUS * RADIUS {
+                    continue;
+                }
+                assert_eq!(sees(from_a, b), sees(from_b, a), "between {a:?} and {b:?}");
+            }
+        }
+    }
+}
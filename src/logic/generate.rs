@@ -1,10 +1,59 @@
 use std::cmp::{max, min};
+use std::{error, fmt};
 
-use log::info;
+use log::{info, warn};
 use rand::{Rng, SeedableRng, rngs::SmallRng};
 
-use super::{Entity, Glyph, Tile, level::Level};
+use super::ai::{Ai, Behavior};
+use super::items::Item;
+use super::level::{Level, RoomKind};
+use super::path::DijkstraMap;
+use super::{Entity, Glyph, Tile, combat::Fighter};
 
+mod bsp;
+mod castle;
+mod caves;
+mod rooms;
+mod vaults;
+mod walk;
+
+pub use vaults::select as select_vaults;
+
+/// A way of laying out the walls and floors of a level.
+trait Generator {
+    /// Carve a map of the given size, with its entry point, and split its
+    /// floor into areas to be furnished and populated.
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String>;
+}
+
+/// Part of a map that gets its own share of monsters, items and torches.
+struct Area {
+    tiles: Vec<(usize, usize)>,
+    kind: RoomKind,
+}
+
+impl Area {
+    fn pick_xy(&self, rng: &mut impl Rng) -> (usize, usize) {
+        self.tiles[rng.random_range(0..self.tiles.len())]
+    }
+
+    /// Top left and bottom right corners of the smallest rectangle around the area.
+    fn bounds(&self) -> ((usize, usize), (usize, usize)) {
+        let xs = self.tiles.iter().map(|&(x, _)| x);
+        let ys = self.tiles.iter().map(|&(_, y)| y);
+        (
+            (xs.clone().min().unwrap(), ys.clone().min().unwrap()),
+            (xs.max().unwrap(), ys.max().unwrap()),
+        )
+    }
+}
+
+/// A rectangle of floor surrounded by walls, corners included.
 struct Room {
     x0: usize,
     y0: usize,
@@ -34,6 +83,16 @@ impl Room {
         }
     }
 
+    /// The room whose only tile inside is `(x, y)`, to tunnel to or from a point.
+    fn around((x, y): (usize, usize)) -> Self {
+        Room {
+            x0: x - 1,
+            y0: y - 1,
+            x1: x + 1,
+            y1: y + 1,
+        }
+    }
+
     fn intersects(&self, other: &Room) -> bool {
         self.x0 <= other.x1 && self.x1 >= other.x0 && self.y0 <= other.y1 && self.y1 >= other.y0
     }
@@ -66,80 +125,395 @@ impl Room {
         draw_line(xm, ym, xb, yb, level);
     }
 
+    /// Hang doors in about half of the gaps tunnelled through the walls.
+    fn hang_doors(&self, level: &mut Level, rng: &mut impl Rng) {
+        let across = (self.x0 + 1..self.x1).flat_map(|x| [(x, self.y0), (x, self.y1)]);
+        let down = (self.y0 + 1..self.y1).flat_map(|y| [(self.x0, y), (self.x1, y)]);
+        for (x, y) in across.chain(down) {
+            // only in gaps one tile wide, with the wall either side
+            let sides = if y == self.y0 || y == self.y1 {
+                [(x - 1, y), (x + 1, y)]
+            } else {
+                [(x, y - 1), (x, y + 1)]
+            };
+            if level.get_tile(x, y) == Tile::Floor
+                && sides
+                    .iter()
+                    .all(|&(x, y)| level.get_tile(x, y) == Tile::Wall)
+                && rng.random()
+            {
+                level.set_tile(x, y, Tile::DoorClosed);
+            }
+        }
+    }
+
     fn pick_xy(&self, rng: &mut impl Rng) -> (usize, usize) {
         let x = rng.random_range(self.x0 + 1..=self.x1 - 1);
         let y = rng.random_range(self.y0 + 1..=self.y1 - 1);
         (x, y)
     }
+
+    fn area(&self, kind: RoomKind) -> Area {
+        let rows = self.y0 + 1..=self.y1 - 1;
+        let tiles = rows.flat_map(|y| (self.x0 + 1..=self.x1 - 1).map(move |x| (x, y)));
+        Area {
+            tiles: tiles.collect(),
+            kind,
+        }
+    }
+}
+
+/// How many maps are drawn for a level before giving up.
+const MAX_ATTEMPTS: usize = 10;
+
+/// No playable map could be drawn for a level.
+#[derive(Debug)]
+pub struct GenerationError {
+    pub depth: usize,
+    /// Why the last map drawn was discarded.
+    pub reason: String,
 }
 
-pub fn generate_level() -> Level {
-    let width = 80;
-    let height = 38;
-    let mut rng = seeded_rng();
-    let mut rooms: Vec<Room> = Vec::new();
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot generate depth {}: {}", self.depth, self.reason)
+    }
+}
+
+impl error::Error for GenerationError {}
 
-    'outer: for _ in 0..40 {
-        let room = Room::random(6, 6, width / 3, height / 3, width, height, &mut rng);
-        for other_room in &rooms {
-            if room.intersects(other_room) {
-                continue 'outer;
+/// Generate the level at `depth`, fully reachable from its entry point.
+///
+/// Maps that fail validation are discarded and drawn again from the same
+/// random stream, so the result still only depends on the seed.
+pub fn generate_level(seed: u64, depth: usize) -> Result<Level, GenerationError> {
+    let mut rng = seeded_rng(seed, depth);
+    let mut reason = String::new();
+    for _ in 0..MAX_ATTEMPTS {
+        match generate_map(depth, &mut rng) {
+            Ok(level) => return Ok(level),
+            Err(e) => {
+                warn!("Discarding map at depth {depth}: {e}");
+                reason = e;
             }
         }
-        rooms.push(room);
     }
+    Err(GenerationError { depth, reason })
+}
+
+/// The castle halls give way to older and rougher diggings further down.
+fn generator_for(depth: usize, rng: &mut impl Rng) -> &'static dyn Generator {
+    match depth {
+        1 => &castle::Castle,
+        2 => &rooms::Rooms,
+        3..=4 => &bsp::Bsp,
+        5 => &walk::DrunkardsWalk,
+        6 => &caves::Caves,
+        _ => match rng.random_range(0..5) {
+            0 => &castle::Castle,
+            1 => &rooms::Rooms,
+            2 => &bsp::Bsp,
+            3 => &walk::DrunkardsWalk,
+            _ => &caves::Caves,
+        },
+    }
+}
 
-    let entry_point = rooms[0].pick_xy(&mut rng);
-    let mut level = Level::new(width, height, entry_point);
+fn generate_map(depth: usize, rng: &mut SmallRng) -> Result<Level, String> {
+    // the castle sprawls wider the deeper one goes
+    let width = (80 + 40 * (depth - 1)).min(240);
+    let height = (38 + 12 * (depth - 1)).min(86);
+    let (mut level, areas) = generator_for(depth, rng).carve(width, height, rng)?;
+    vaults::place_vaults(&mut level, &areas, depth, rng);
+    connect_regions(&mut level);
 
-    for room in &rooms {
-        room.carve(&mut level);
+    // the stairs down are as far as possible from the way in
+    let entry_point = level.entry();
+    let distances = DijkstraMap::new(&level, &[entry_point]);
+    let tiles = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+    let exit_point = tiles
+        .filter(|&(x, y)| level.get_tile(x, y) == Tile::Floor)
+        .max_by_key(|&(x, y)| distances.get(x, y))
+        .filter(|&point| point != entry_point)
+        .ok_or("no room for the stairs down")?;
+    if depth > 1 {
+        level.set_tile(entry_point.0, entry_point.1, Tile::UpStairs);
+    }
+    level.set_tile(exit_point.0, exit_point.1, Tile::DownStairs);
+    validate(&level)?;
+
+    for area in &areas {
+        place_pool(area, depth, &mut level, rng);
+    }
+    for area in &areas {
+        let (top_left, bottom_right) = area.bounds();
+        level.add_room(top_left, bottom_right, area.kind);
+        place_torches(area, depth, &mut level, rng);
+        place_monsters(area, depth, &mut level, rng);
+        place_items(area, &mut level, rng);
     }
 
-    for room1_room2 in rooms.windows(2) {
-        if let [room1, room2] = room1_room2 {
-            room1.tunnel_to(room2, &mut level, &mut rng);
+    Ok(level)
+}
+
+/// Tunnel from every walkable area cut off from the entry point to the
+/// nearest tile that can be reached.
+fn connect_regions(level: &mut Level) {
+    loop {
+        let reachable = DijkstraMap::new(level, &[level.entry()]);
+        let tiles = (0..level.height()).flat_map(|y| (0..level.width()).map(move |x| (x, y)));
+        let Some(lost) = tiles
+            .clone()
+            .find(|&(x, y)| level.get_tile(x, y).is_walkable() && reachable.get(x, y).is_none())
+        else {
+            return;
+        };
+        let (x, y) = tiles
+            .filter(|&(x, y)| reachable.get(x, y).is_some())
+            .min_by_key(|&(x, y)| x.abs_diff(lost.0).max(y.abs_diff(lost.1)))
+            .unwrap();
+        info!("Connecting {lost:?} to {:?}", (x, y));
+        draw_line(lost.0, lost.1, x, lost.1, level);
+        draw_line(x, lost.1, x, y, level);
+    }
+}
+
+/// Fill part of an area with water, or with lava or a chasm further down,
+/// unless that would cut off some of the level from the entry point.
+fn place_pool(area: &Area, depth: usize, level: &mut Level, rng: &mut impl Rng) {
+    let chance = match area.kind {
+        // a well, or a fountain
+        RoomKind::Courtyard => 2,
+        RoomKind::Cave => 4,
+        RoomKind::Chamber => 8,
+        _ => return,
+    };
+    if !rng.random_ratio(1, chance) {
+        return;
+    }
+    let tile = match rng.random_range(0..3) {
+        0 if depth >= 5 => Tile::Lava,
+        1 if depth >= 3 => Tile::Chasm,
+        _ => Tile::Water,
+    };
+    let (cx, cy) = area.pick_xy(rng);
+    let radius = rng.random_range(1..=3_usize);
+    let mut pool = Vec::new();
+    for &(x, y) in &area.tiles {
+        let (dx, dy) = (x.abs_diff(cx), y.abs_diff(cy));
+        // ragged edges
+        if dx * dx + dy * dy <= radius * radius + rng.random_range(0..=radius)
+            && level.get_tile(x, y) == Tile::Floor
+            && (x, y) != level.entry()
+            && !level.is_occupied(x, y)
+            && level.item_at(x, y).is_none()
+        {
+            pool.push((x, y));
+            level.set_tile(x, y, tile);
+        }
+    }
+    if !is_safely_connected(level) {
+        for (x, y) in pool {
+            level.set_tile(x, y, Tile::Floor);
+        }
+    }
+}
+
+/// Whether every walkable tile that does no harm can be reached from the
+/// entry point without being hurt on the way.
+fn is_safely_connected(level: &Level) -> bool {
+    let safe = |tile: Tile| {
+        (tile.is_walkable() || tile.opened().is_some()) && tile.properties().damage <= 0
+    };
+    let width = level.width();
+    let mut seen = vec![false; width * level.height()];
+    let (ex, ey) = level.entry();
+    let mut stack = vec![(ex, ey)];
+    seen[ey * width + ex] = true;
+    while let Some((x, y)) = stack.pop() {
+        for (nx, ny) in (y.saturating_sub(1)..=y + 1)
+            .flat_map(|ny| (x.saturating_sub(1)..=x + 1).map(move |nx| (nx, ny)))
+            .filter(|&(nx, ny)| nx < width && ny < level.height())
+        {
+            if !seen[ny * width + nx] && safe(level.get_tile(nx, ny)) {
+                seen[ny * width + nx] = true;
+                stack.push((nx, ny));
+            }
         }
     }
+    (0..level.height())
+        .all(|y| (0..width).all(|x| seen[y * width + x] || !safe(level.get_tile(x, y))))
+}
 
-    for room in &rooms {
-        place_monsters(room, &mut level, &mut rng);
+/// Check that the map can be played through: every walkable tile, the
+/// stairs down in particular, can be reached from the entry point.
+fn validate(level: &Level) -> Result<(), String> {
+    let (ex, ey) = level.entry();
+    if !level.get_tile(ex, ey).is_walkable() {
+        return Err("the entry point is not walkable".to_string());
     }
+    let Some((sx, sy)) = level.exit() else {
+        return Err("there are no stairs down".to_string());
+    };
+    let reachable = DijkstraMap::new(level, &[(ex, ey)]);
+    if reachable.get(sx, sy).is_none() {
+        return Err("the stairs down cannot be reached".to_string());
+    }
+    for y in 0..level.height() {
+        for x in 0..level.width() {
+            if level.get_tile(x, y).is_walkable() && reachable.get(x, y).is_none() {
+                return Err(format!("tile {x},{y} cannot be reached"));
+            }
+        }
+    }
+    Ok(())
+}
 
-    level
+fn seeded_rng(seed: u64, depth: usize) -> SmallRng {
+    info!("Level seed is 0x{seed:08X?}, depth {depth}");
+    // each depth gets its own independent stream
+    SmallRng::seed_from_u64(seed ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
 }
 
-fn seeded_rng() -> impl Rng {
-    let seed: u64 = rand::rng().random();
-    info!("Level seed is 0x{seed:08X?}");
-    SmallRng::seed_from_u64(seed)
+/// Split the floor of a map into areas of roughly `size` by `size / 2` tiles.
+///
+/// For maps without rooms; bits of floor too small to matter are left out.
+fn sector_areas(level: &Level, size: usize) -> Vec<Area> {
+    let (width, height) = (size, size / 2);
+    let mut areas = Vec::new();
+    for y0 in (0..level.height()).step_by(height) {
+        for x0 in (0..level.width()).step_by(width) {
+            let rows = y0..(y0 + height).min(level.height());
+            let tiles: Vec<(usize, usize)> = rows
+                .flat_map(|y| (x0..(x0 + width).min(level.width())).map(move |x| (x, y)))
+                .filter(|&(x, y)| level.get_tile(x, y) == Tile::Floor)
+                .collect();
+            if tiles.len() >= size {
+                areas.push(Area {
+                    tiles,
+                    kind: RoomKind::Cave,
+                });
+            }
+        }
+    }
+    areas
 }
 
+/// Dig a straight tunnel through the walls between two points.
 fn draw_line(x1: usize, y1: usize, x2: usize, y2: usize, level: &mut Level) {
     debug_assert!(x1 == x2 || y1 == y2);
-    if x1 == x2 {
-        for y in min(y1, y2)..=max(y1, y2) {
-            level.set_tile(x1, y, Tile::Floor);
-        }
+    let points: Vec<(usize, usize)> = if x1 == x2 {
+        (min(y1, y2)..=max(y1, y2)).map(|y| (x1, y)).collect()
     } else {
-        for x in min(x1, x2)..=max(x1, x2) {
-            level.set_tile(x, y1, Tile::Floor);
+        (min(x1, x2)..=max(x1, x2)).map(|x| (x, y1)).collect()
+    };
+    for (x, y) in points {
+        // leave stairs and doors in place
+        let tile = level.get_tile(x, y);
+        if !tile.is_walkable() && tile.opened().is_none() {
+            level.set_tile(x, y, Tile::Floor);
+        }
+    }
+}
+
+fn place_torches(area: &Area, depth: usize, level: &mut Level, rng: &mut impl Rng) {
+    let torches = match area.kind {
+        // halls of state are always brightly lit
+        RoomKind::GreatHall | RoomKind::ThroneRoom => 4,
+        RoomKind::Courtyard => 0,
+        _ => {
+            // the lower floors are darker
+            let lit_chance = 6_u32.saturating_sub(depth as u32).max(1);
+            if !rng.random_ratio(lit_chance, 6) {
+                return;
+            }
+            rng.random_range(1..=2)
+        }
+    };
+    for _ in 0..torches {
+        // on a wall next to the floor of the area
+        let (x, y) = area.pick_xy(rng);
+        let walls: Vec<(usize, usize)> = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .filter(|&(x, y)| level.get_tile(x, y) == Tile::Wall)
+            .collect();
+        if !walls.is_empty() {
+            let (x, y) = walls[rng.random_range(0..walls.len())];
+            level.set_tile(x, y, Tile::Torch);
         }
     }
 }
 
-fn place_monsters(room: &Room, level: &mut Level, rng: &mut impl Rng) {
-    for _ in 0..rng.random_range(0..=2) {
-        let (x, y) = room.pick_xy(rng);
-        if level.actors().iter().any(|e| e.pos() == (x, y)) {
+fn place_monsters(area: &Area, depth: usize, level: &mut Level, rng: &mut impl Rng) {
+    // deeper levels are more crowded, and with a larger share of hobgoblins
+    let max_monsters = 2 + (depth - 1) / 2;
+    let hobgoblin_chance = depth.min(4) as u32;
+    let (count, hobgoblin_chance, asleep_chance) = match area.kind {
+        // soldiers off duty, most of them sleeping
+        RoomKind::Barracks => (rng.random_range(1..=max_monsters), hobgoblin_chance, 4),
+        // the guard is all hobgoblins, and wide awake
+        RoomKind::ThroneRoom => (rng.random_range(1..=max_monsters), 5, 1),
+        // passing through
+        RoomKind::Corridor | RoomKind::Courtyard | RoomKind::Tower => {
+            (rng.random_ratio(1, 3) as usize, hobgoblin_chance, 3)
+        }
+        _ => (rng.random_range(0..=max_monsters), hobgoblin_chance, 3),
+    };
+    for _ in 0..count {
+        let (x, y) = area.pick_xy(rng);
+        if (x, y) == level.entry()
+            || level.get_tile(x, y) != Tile::Floor
+            || level.actors().iter().any(|e| e.pos() == (x, y))
+        {
             continue;
         }
-        let glyph = if rng.random_ratio(4, 5) {
-            Glyph::Goblin
+        // most monsters are caught napping, and can be sneaked past
+        let new_ai = if rng.random_ratio(asleep_chance, 5) {
+            Ai::asleep
+        } else {
+            Ai::new
+        };
+        let monster = if !rng.random_ratio(hobgoblin_chance, 5) {
+            goblin(x, y, new_ai(Behavior::Cowardly))
         } else {
-            Glyph::Hobgobin
+            hobgoblin(x, y, new_ai(Behavior::Relentless))
         };
-        let goblin = Entity::new(x, y, glyph);
-        level.add_actor(goblin);
+        level.add_actor(monster);
+    }
+}
+
+fn goblin(x: usize, y: usize, ai: Ai) -> Entity {
+    Entity::new(x, y, Glyph::Goblin, Fighter::new(10, 3, 0), 6, Some(ai))
+}
+
+fn hobgoblin(x: usize, y: usize, ai: Ai) -> Entity {
+    let mut hobgoblin = Entity::new(x, y, Glyph::Hobgobin, Fighter::new(16, 4, 1), 7, Some(ai));
+    // hobgoblins carry torches, and can be seen coming in the dark
+    hobgoblin.light = 3;
+    hobgoblin
+}
+
+fn place_items(area: &Area, level: &mut Level, rng: &mut impl Rng) {
+    let count = match area.kind {
+        // treasure is kept up in the towers and close to the throne
+        RoomKind::Tower | RoomKind::ThroneRoom => rng.random_range(1..=2),
+        RoomKind::Corridor => 0,
+        _ => rng.random_range(0..=1),
+    };
+    for _ in 0..count {
+        let (x, y) = area.pick_xy(rng);
+        if level.get_tile(x, y) != Tile::Floor || level.item_at(x, y).is_some() {
+            continue;
+        }
+        level.add_item(x, y, random_item(rng));
+    }
+}
+
+fn random_item(rng: &mut impl Rng) -> Item {
+    match rng.random_range(0..100) {
+        0..60 => Item::HealingPotion,
+        60..75 => Item::LightningScroll,
+        75..85 => Item::MappingScroll,
+        _ => Item::OilFlask,
     }
 }
@@ -0,0 +1,99 @@
+use rand::{Rng, rngs::SmallRng};
+
+use super::{Area, Generator, Level, Room, RoomKind};
+
+/// Smallest part of the map that can hold a room, walls included.
+const MIN_WIDTH: usize = 20;
+const MIN_HEIGHT: usize = 12;
+
+/// Rooms in the cells of a binary space partition, so that they fill the
+/// map evenly, with neighbouring cells tunnelled together.
+pub struct Bsp;
+
+impl Generator for Bsp {
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String> {
+        if width < MIN_WIDTH || height < MIN_HEIGHT {
+            return Err("the map is too small to be partitioned".to_string());
+        }
+        let mut rooms = Vec::new();
+        let mut tunnels = Vec::new();
+        split((0, 0, width, height), rng, &mut rooms, &mut tunnels);
+
+        let entry_point = rooms[0].pick_xy(rng);
+        let mut level = Level::new(width, height, entry_point);
+        for room in &rooms {
+            room.carve(&mut level);
+        }
+        for (a, b) in tunnels {
+            rooms[a].tunnel_to(&rooms[b], &mut level, rng);
+        }
+        for room in &rooms {
+            room.hang_doors(&mut level, rng);
+        }
+
+        Ok((
+            level,
+            rooms
+                .iter()
+                .map(|room| room.area(RoomKind::Chamber))
+                .collect(),
+        ))
+    }
+}
+
+/// Partition the cell `(x, y, width, height)` until it is too small to be
+/// split, putting a room in each leaf.
+///
+/// Returns the indices of the rooms made, after recording in `tunnels` the
+/// pairs of rooms joining the two halves of every split.
+fn split(
+    (x, y, width, height): (usize, usize, usize, usize),
+    rng: &mut impl Rng,
+    rooms: &mut Vec<Room>,
+    tunnels: &mut Vec<(usize, usize)>,
+) -> Vec<usize> {
+    let can_split_x = width >= 2 * MIN_WIDTH;
+    let can_split_y = height >= 2 * MIN_HEIGHT;
+    // the longer side is more likely to be cut
+    let split_x = match (can_split_x, can_split_y) {
+        (false, false) => {
+            rooms.push(room_in((x, y, width, height), rng));
+            return vec![rooms.len() - 1];
+        }
+        (true, true) => rng.random_ratio(width as u32, (width + height) as u32),
+        (split_x, _) => split_x,
+    };
+    let (first, second) = if split_x {
+        let w = rng.random_range(MIN_WIDTH..=width - MIN_WIDTH);
+        ((x, y, w, height), (x + w, y, width - w, height))
+    } else {
+        let h = rng.random_range(MIN_HEIGHT..=height - MIN_HEIGHT);
+        ((x, y, width, h), (x, y + h, width, height - h))
+    };
+    let mut first = split(first, rng, rooms, tunnels);
+    let second = split(second, rng, rooms, tunnels);
+    let a = first[rng.random_range(0..first.len())];
+    let b = second[rng.random_range(0..second.len())];
+    tunnels.push((a, b));
+    first.extend(second);
+    first
+}
+
+/// A room of random size and position within a cell.
+fn room_in((x, y, width, height): (usize, usize, usize, usize), rng: &mut impl Rng) -> Room {
+    let w = rng.random_range(6..=width);
+    let h = rng.random_range(6..=height);
+    let x0 = rng.random_range(x..=x + width - w);
+    let y0 = rng.random_range(y..=y + height - h);
+    Room {
+        x0,
+        y0,
+        x1: x0 + w - 1,
+        y1: y0 + h - 1,
+    }
+}
@@ -0,0 +1,178 @@
+use rand::{Rng, rngs::SmallRng};
+
+use super::{Area, Generator, Level, Room, RoomKind, Tile};
+
+/// Width of the corridor running around the courtyard.
+const CORRIDOR: usize = 2;
+
+/// A castle ward: a courtyard ringed by a corridor, with wings along the
+/// outer walls and a tower in each corner.
+///
+/// The great hall takes the north wing, the barracks the south wing and
+/// the throne room the east wing, with smaller chambers filling the rest.
+pub struct Castle;
+
+impl Generator for Castle {
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String> {
+        // towers and wings are the same depth, walls included
+        let tower = rng.random_range(6..=8);
+        let room_size = rng.random_range(12..=16);
+        let inner = Room {
+            x0: tower - 1,
+            y0: tower - 1,
+            x1: width - tower,
+            y1: height - tower,
+        };
+        let courtyard = Room {
+            x0: inner.x0 + CORRIDOR + 1,
+            y0: inner.y0 + CORRIDOR + 1,
+            x1: inner.x1 - CORRIDOR - 1,
+            y1: inner.y1 - CORRIDOR - 1,
+        };
+        if courtyard.x1 < courtyard.x0 + 6 || courtyard.y1 < courtyard.y0 + 4 {
+            return Err("the castle does not fit on the map".to_string());
+        }
+
+        // rooms along the outer walls, sharing their side walls
+        let mut wings: Vec<(Room, RoomKind)> = Vec::new();
+        let great_hall = rng.random_range(30..=40);
+        for (x0, x1, kind) in centered(inner.x0, inner.x1, great_hall, room_size) {
+            let kind = kind.unwrap_or(RoomKind::GreatHall);
+            wings.push((room(x0, 0, x1, inner.y0), kind));
+        }
+        for (x0, x1) in split(inner.x0, inner.x1, room_size) {
+            wings.push((room(x0, inner.y1, x1, height - 1), RoomKind::Barracks));
+        }
+        for (y0, y1) in split(inner.y0, inner.y1, room_size / 2) {
+            wings.push((room(0, y0, inner.x0, y1), RoomKind::Chamber));
+        }
+        for (y0, y1, kind) in centered(inner.y0, inner.y1, 20, room_size / 2) {
+            let kind = kind.unwrap_or(RoomKind::ThroneRoom);
+            wings.push((room(inner.x1, y0, width - 1, y1), kind));
+        }
+        let towers = [
+            room(0, 0, inner.x0, inner.y0),
+            room(inner.x1, 0, width - 1, inner.y0),
+            room(0, inner.y1, inner.x0, height - 1),
+            room(inner.x1, inner.y1, width - 1, height - 1),
+        ];
+
+        let entry_point = courtyard.pick_xy(rng);
+        let mut level = Level::new(width, height, entry_point);
+        inner.carve(&mut level);
+        courtyard.build(&mut level);
+        for (room, _) in &wings {
+            room.carve(&mut level);
+        }
+        for tower in &towers {
+            tower.carve(&mut level);
+        }
+
+        // a wide gate in the middle of each side of the courtyard
+        let (cx, cy) = (
+            (courtyard.x0 + courtyard.x1) / 2,
+            (courtyard.y0 + courtyard.y1) / 2,
+        );
+        for d in 0..CORRIDOR {
+            level.set_tile(cx + d, courtyard.y0, Tile::Floor);
+            level.set_tile(cx + d, courtyard.y1, Tile::Floor);
+            level.set_tile(courtyard.x0, cy + d, Tile::Floor);
+            level.set_tile(courtyard.x1, cy + d, Tile::Floor);
+        }
+        // a door from each wing room to the corridor
+        for (room, _) in &wings {
+            let across = rng.random_range(room.x0 + 1..=room.x1 - 1);
+            let down = rng.random_range(room.y0 + 1..=room.y1 - 1);
+            let door = if room.y1 == inner.y0 {
+                (across, room.y1)
+            } else if room.y0 == inner.y1 {
+                (across, room.y0)
+            } else if room.x1 == inner.x0 {
+                (room.x1, down)
+            } else {
+                (room.x0, down)
+            };
+            level.set_tile(door.0, door.1, Tile::DoorClosed);
+        }
+        // towers are entered from the north and south wings, and their
+        // treasure is often kept under lock
+        for tower in &towers {
+            let x = if tower.x0 == 0 { tower.x1 } else { tower.x0 };
+            let y = rng.random_range(tower.y0 + 1..=tower.y1 - 1);
+            let door = if rng.random() {
+                Tile::DoorLocked
+            } else {
+                Tile::DoorClosed
+            };
+            level.set_tile(x, y, door);
+        }
+
+        // the courtyard comes first, as it holds the entry point
+        let mut areas = vec![courtyard.area(RoomKind::Courtyard)];
+        areas.extend(wings.iter().map(|(room, kind)| room.area(*kind)));
+        areas.extend(towers.iter().map(|tower| tower.area(RoomKind::Tower)));
+        let in_courtyard = |(x, y): (usize, usize)| {
+            (courtyard.x0..=courtyard.x1).contains(&x) && (courtyard.y0..=courtyard.y1).contains(&y)
+        };
+        let mut corridor = inner.area(RoomKind::Corridor);
+        corridor.tiles.retain(|&pos| !in_courtyard(pos));
+        areas.push(corridor);
+        Ok((level, areas))
+    }
+}
+
+fn room(x0: usize, y0: usize, x1: usize, y1: usize) -> Room {
+    Room { x0, y0, x1, y1 }
+}
+
+impl Room {
+    /// Put up the walls of the room, then clear its floor.
+    fn build(&self, level: &mut Level) {
+        for y in self.y0..=self.y1 {
+            for x in self.x0..=self.x1 {
+                level.set_tile(x, y, Tile::Wall);
+            }
+        }
+        self.carve(level);
+    }
+}
+
+/// Split the wall from `start` to `end` into stretches of about `size`
+/// tiles, for rooms that share their walls.
+fn split(start: usize, end: usize, size: usize) -> Vec<(usize, usize)> {
+    let count = ((end - start) / size).max(1);
+    let bounds: Vec<usize> = (0..=count)
+        .map(|i| start + (end - start) * i / count)
+        .collect();
+    bounds.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// A stretch of about `size` tiles in the middle of the wall from `start`
+/// to `end`, with chambers either side if there is room for them.
+///
+/// The middle stretch is the one without a room kind.
+fn centered(
+    start: usize,
+    end: usize,
+    size: usize,
+    room_size: usize,
+) -> Vec<(usize, usize, Option<RoomKind>)> {
+    if end - start < size + 2 * room_size {
+        return vec![(start, end, None)];
+    }
+    let a = (start + end - size) / 2;
+    let b = a + size;
+    let chamber = |(x0, x1)| (x0, x1, Some(RoomKind::Chamber));
+    let mut stretches: Vec<_> = split(start, a, room_size)
+        .into_iter()
+        .map(chamber)
+        .collect();
+    stretches.push((a, b, None));
+    stretches.extend(split(b, end, room_size).into_iter().map(chamber));
+    stretches
+}
@@ -0,0 +1,86 @@
+use rand::{Rng, rngs::SmallRng};
+
+use super::{Area, Generator, Level, Tile, sector_areas};
+
+/// Chance out of 100 that a tile starts out as rock.
+const ROCK_CHANCE: u32 = 45;
+/// Rounds of smoothing of the initial noise.
+const STEPS: usize = 5;
+/// Caverns smaller than this, in tiles, are filled in.
+const MIN_CAVERN: usize = 20;
+
+/// Natural caverns grown by a cellular automaton from random noise.
+pub struct Caves;
+
+impl Generator for Caves {
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String> {
+        let mut rock = vec![true; width * height];
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                rock[y * width + x] = rng.random_ratio(ROCK_CHANCE, 100);
+            }
+        }
+        for _ in 0..STEPS {
+            let prev = rock.clone();
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    // rock spreads where it has the majority, the tile itself included
+                    let count = (y - 1..=y + 1)
+                        .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                        .filter(|&(nx, ny)| prev[ny * width + nx])
+                        .count();
+                    rock[y * width + x] = count >= 5;
+                }
+            }
+        }
+
+        let caverns: Vec<Vec<(usize, usize)>> = caverns(&rock, width, height)
+            .into_iter()
+            .filter(|cavern| cavern.len() >= MIN_CAVERN)
+            .collect();
+        let Some(largest) = caverns.iter().max_by_key(|cavern| cavern.len()) else {
+            return Err("the caverns are too small".to_string());
+        };
+        let entry_point = largest[rng.random_range(0..largest.len())];
+        let mut level = Level::new(width, height, entry_point);
+        for &(x, y) in caverns.iter().flatten() {
+            level.set_tile(x, y, Tile::Floor);
+        }
+
+        let areas = sector_areas(&level, 32);
+        Ok((level, areas))
+    }
+}
+
+/// The tiles of every open region, 8-way connected.
+fn caverns(rock: &[bool], width: usize, height: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = rock.to_vec();
+    let mut caverns = Vec::new();
+    for start in 0..rock.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut cavern = Vec::new();
+        let mut stack = vec![(start % width, start / width)];
+        while let Some((x, y)) = stack.pop() {
+            cavern.push((x, y));
+            // open tiles are never on the edge of the map
+            for ny in y - 1..=y + 1 {
+                for nx in x - 1..=x + 1 {
+                    if nx < width && ny < height && !seen[ny * width + nx] {
+                        seen[ny * width + nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        caverns.push(cavern);
+    }
+    caverns
+}
@@ -0,0 +1,55 @@
+use rand::rngs::SmallRng;
+
+use super::{Area, Generator, Level, Room, RoomKind};
+
+/// Rooms scattered at random, each one tunnelled to the next.
+pub struct Rooms;
+
+impl Generator for Rooms {
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String> {
+        let attempts = 40 * (width * height) / (80 * 38);
+        let mut rooms: Vec<Room> = Vec::new();
+
+        'outer: for _ in 0..attempts {
+            let room = Room::random(6, 6, 80 / 3, 38 / 3, width, height, rng);
+            for other_room in &rooms {
+                if room.intersects(other_room) {
+                    continue 'outer;
+                }
+            }
+            rooms.push(room);
+        }
+        let Some(entry_room) = rooms.first() else {
+            return Err("no room fits on the map".to_string());
+        };
+
+        let entry_point = entry_room.pick_xy(rng);
+        let mut level = Level::new(width, height, entry_point);
+
+        for room in &rooms {
+            room.carve(&mut level);
+        }
+
+        for room1_room2 in rooms.windows(2) {
+            if let [room1, room2] = room1_room2 {
+                room1.tunnel_to(room2, &mut level, rng);
+            }
+        }
+        for room in &rooms {
+            room.hang_doors(&mut level, rng);
+        }
+
+        Ok((
+            level,
+            rooms
+                .iter()
+                .map(|room| room.area(RoomKind::Chamber))
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,415 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use std::{fs, io, iter};
+
+use rand::Rng;
+
+use super::{Ai, Area, Behavior, Level, Room, RoomKind, Tile, goblin, hobgoblin, random_item};
+
+/// Hand-drawn vaults that come with the game, by name.
+const BUILT_IN: [(&str, &str); 4] = [
+    (
+        "guard-post",
+        include_str!("../../../vaults/guard-post.vault"),
+    ),
+    ("shrine", include_str!("../../../vaults/shrine.vault")),
+    ("armory", include_str!("../../../vaults/armory.vault")),
+    ("treasury", include_str!("../../../vaults/treasury.vault")),
+];
+
+/// Spots tried for each vault before leaving it out of a level.
+const PLACEMENT_ATTEMPTS: usize = 20;
+
+static VAULTS: OnceLock<Vec<(String, Vault)>> = OnceLock::new();
+
+/// A piece of map drawn by hand, with the rules for placing it.
+struct Vault {
+    min_depth: usize,
+    max_depth: usize,
+    /// Chance out of 100 of appearing on a level within its depths.
+    chance: u32,
+    rows: Vec<Vec<char>>,
+}
+
+impl Vault {
+    /// Read a vault: `key = value` lines, then the map after a `---` line.
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut vault = Vault {
+            min_depth: 1,
+            max_depth: usize::MAX,
+            chance: 100,
+            rows: Vec::new(),
+        };
+        let mut lines = text.lines().enumerate();
+        for (n, line) in lines.by_ref() {
+            let line = line.trim();
+            if line == "---" {
+                break;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => vault.set(key.trim(), value.trim()),
+                None => Err("expected 'key = value'".to_string()),
+            };
+            result.map_err(|e| format!("line {}: {e}", n + 1))?;
+        }
+        for (n, line) in lines {
+            if let Some(ch) = line.chars().find(|ch| !" #.+=gH!*".contains(*ch)) {
+                return Err(format!("line {}: unknown map character '{ch}'", n + 1));
+            }
+            vault.rows.push(line.trim_end().chars().collect());
+        }
+        let width = vault.rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width == 0 {
+            return Err("missing map".to_string());
+        }
+        for row in &mut vault.rows {
+            row.resize(width, ' ');
+        }
+        if !vault.rows.iter().flatten().any(|&ch| is_door(ch)) {
+            return Err("no door to join the vault to the level".to_string());
+        }
+        Ok(vault)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("expected a number in '{value}'"))
+        };
+        match key {
+            "depth" => match value.split_once('-') {
+                Some((min, "")) => self.min_depth = number(min)?,
+                Some((min, max)) => (self.min_depth, self.max_depth) = (number(min)?, number(max)?),
+                None => (self.min_depth, self.max_depth) = (number(value)?, number(value)?),
+            },
+            "chance" => self.chance = number(value)?.min(100) as u32,
+            _ => return Err(format!("unknown key '{key}'")),
+        }
+        Ok(())
+    }
+
+    /// The map turned a quarter `turns` times clockwise, then mirrored if asked.
+    fn oriented(&self, turns: usize, mirror: bool) -> Vec<Vec<char>> {
+        let mut rows = self.rows.clone();
+        for _ in 0..turns {
+            let height = rows.len();
+            rows = (0..rows[0].len())
+                .map(|x| (0..height).rev().map(|y| rows[y][x]).collect())
+                .collect();
+        }
+        if mirror {
+            rows.iter_mut().for_each(|row| row.reverse());
+        }
+        rows
+    }
+}
+
+/// Use the vaults of the `.vault` files in a directory instead of the
+/// built-in ones, before any level is generated.
+pub fn select(dir: &Path) -> Result<(), io::Error> {
+    let vaults = read_vaults(dir)?;
+    VAULTS.set(vaults).map_err(|_| {
+        let msg = format!(
+            "cannot use the vaults in {}, others are already in use",
+            dir.display()
+        );
+        io::Error::new(io::ErrorKind::AlreadyExists, msg)
+    })
+}
+
+fn read_vaults(dir: &Path) -> Result<Vec<(String, Vault)>, io::Error> {
+    let mut vaults = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != "vault")
+        {
+            continue;
+        }
+        let vault = Vault::parse(&fs::read_to_string(&path)?).map_err(|e| {
+            let msg = format!("invalid vault {}: {e}", path.display());
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        })?;
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        vaults.push((name, vault));
+    }
+    // levels depend on the order vaults are tried in, unlike directory listings
+    vaults.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(vaults)
+}
+
+fn current() -> &'static [(String, Vault)] {
+    VAULTS.get_or_init(|| {
+        let parse = |(name, text): &(&str, &str)| (name.to_string(), Vault::parse(text).unwrap());
+        BUILT_IN.iter().map(parse).collect()
+    })
+}
+
+/// Stamp the vaults allowed at `depth` into solid rock, each of its doors
+/// tunnelled to one of the areas of the level.
+pub fn place_vaults(level: &mut Level, areas: &[Area], depth: usize, rng: &mut impl Rng) {
+    for (_, vault) in current() {
+        if !(vault.min_depth..=vault.max_depth).contains(&depth)
+            || !rng.random_ratio(vault.chance, 100)
+        {
+            continue;
+        }
+        let rows = vault.oriented(rng.random_range(0..4), rng.random());
+        let (width, height) = (rows[0].len(), rows.len());
+        // leave room for a ring of rock around the vault, and the map edge
+        if width + 4 > level.width() || height + 4 > level.height() {
+            continue;
+        }
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let x0 = rng.random_range(2..=level.width() - width - 2);
+            let y0 = rng.random_range(2..=level.height() - height - 2);
+            let solid = (y0 - 1..=y0 + height)
+                .all(|y| (x0 - 1..=x0 + width).all(|x| level.get_tile(x, y) == Tile::Wall));
+            if solid {
+                stamp(level, &rows, (x0, y0), rng);
+                connect(level, &rows, (x0, y0), areas, rng);
+                level.add_room((x0, y0), (x0 + width - 1, y0 + height - 1), RoomKind::Vault);
+                break;
+            }
+        }
+    }
+}
+
+fn stamp(level: &mut Level, rows: &[Vec<char>], (x0, y0): (usize, usize), rng: &mut impl Rng) {
+    for (dy, row) in rows.iter().enumerate() {
+        for (dx, &ch) in row.iter().enumerate() {
+            let (x, y) = (x0 + dx, y0 + dy);
+            let tile = match ch {
+                ' ' => continue,
+                '#' => Tile::Wall,
+                '*' => Tile::Torch,
+                '+' => Tile::DoorClosed,
+                '=' => Tile::DoorLocked,
+                _ => Tile::Floor,
+            };
+            level.set_tile(x, y, tile);
+            match ch {
+                'g' => level.add_actor(goblin(x, y, new_ai(Behavior::Cowardly, rng))),
+                'H' => level.add_actor(hobgoblin(x, y, new_ai(Behavior::Relentless, rng))),
+                '!' => level.add_item(x, y, random_item(rng)),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn new_ai(behavior: Behavior, rng: &mut impl Rng) -> Ai {
+    // even behind closed doors, most monsters are caught napping
+    if rng.random_ratio(3, 5) {
+        Ai::asleep(behavior)
+    } else {
+        Ai::new(behavior)
+    }
+}
+
+/// Tunnel from the doors of a vault to the areas of the level, without
+/// cutting through the vault itself.
+fn connect(
+    level: &mut Level,
+    rows: &[Vec<char>],
+    (x0, y0): (usize, usize),
+    areas: &[Area],
+    rng: &mut impl Rng,
+) {
+    if areas.is_empty() {
+        return;
+    }
+    let (width, height) = (rows[0].len(), rows.len());
+    let inside =
+        |(x, y): (usize, usize)| (x0..x0 + width).contains(&x) && (y0..y0 + height).contains(&y);
+    // a tunnel may turn at either corner, so both must stay clear of the vault
+    let clear = |a: (usize, usize), b: (usize, usize)| {
+        [(b.0, a.1), (a.0, b.1)]
+            .into_iter()
+            .all(|corner| !segment(a, corner).chain(segment(corner, b)).any(inside))
+    };
+    // just off the corners of the vault, to go around it
+    let (left, right) = ((x0 - 2).max(1), (x0 + width + 1).min(level.width() - 2));
+    let (top, bottom) = ((y0 - 2).max(1), (y0 + height + 1).min(level.height() - 2));
+    let around = [(left, top), (right, top), (left, bottom), (right, bottom)];
+    let is_open = |dx: usize, dy: usize| {
+        let ch = rows.get(dy).and_then(|row| row.get(dx));
+        ch.is_some_and(|&ch| ch != ' ')
+    };
+    for (dy, row) in rows.iter().enumerate() {
+        for (dx, _) in row.iter().enumerate().filter(|&(_, &ch)| is_door(ch)) {
+            // the side of the door that is out of the vault
+            let outward = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .into_iter()
+                .find(|&(sx, sy)| {
+                    let (nx, ny) = (dx.wrapping_add_signed(sx), dy.wrapping_add_signed(sy));
+                    !is_open(nx, ny)
+                });
+            let Some((sx, sy)) = outward else {
+                continue;
+            };
+            let start = (
+                (x0 + dx).wrapping_add_signed(sx),
+                (y0 + dy).wrapping_add_signed(sy),
+            );
+            level.set_tile(start.0, start.1, Tile::Floor);
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let end = areas[rng.random_range(0..areas.len())].pick_xy(rng);
+                // doors facing away from the end go round the vault, past
+                // one corner or two
+                let route = iter::once(vec![start, end])
+                    .chain(around.iter().map(|&via| vec![start, via, end]))
+                    .chain(
+                        around
+                            .iter()
+                            .flat_map(|&a| around.iter().map(move |&b| vec![start, a, b, end])),
+                    )
+                    .find(|route| route.windows(2).all(|leg| clear(leg[0], leg[1])));
+                if let Some(route) = route {
+                    for leg in route.windows(2) {
+                        Room::around(leg[0]).tunnel_to(&Room::around(leg[1]), level, rng);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn is_door(ch: char) -> bool {
+    ch == '+' || ch == '='
+}
+
+/// Tiles along a horizontal or vertical line, ends included.
+fn segment(a: (usize, usize), b: (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    let xs = a.0.min(b.0)..=a.0.max(b.0);
+    xs.flat_map(move |x| (a.1.min(b.1)..=a.1.max(b.1)).map(move |y| (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::path::DijkstraMap;
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    /// A vault made of a map alone, allowed anywhere.
+    fn vault(map: &str) -> Vault {
+        Vault::parse(&format!("---\n{map}")).unwrap()
+    }
+
+    fn text(rows: &[Vec<char>]) -> Vec<String> {
+        rows.iter().map(|row| row.iter().collect()).collect()
+    }
+
+    #[test]
+    fn built_in_vaults_parse() {
+        for (name, text) in BUILT_IN {
+            if let Err(e) = Vault::parse(text) {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn vault_directory_is_built_in() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("vaults");
+        let mut found: Vec<String> = read_vaults(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let mut built_in: Vec<&str> = BUILT_IN.iter().map(|&(name, _)| name).collect();
+        found.sort();
+        built_in.sort();
+        assert_eq!(found, built_in);
+    }
+
+    #[test]
+    fn vaults_turn_clockwise_then_mirror() {
+        let vault = vault("#+#\n#.g");
+        assert_eq!(text(&vault.oriented(0, false)), ["#+#", "#.g"]);
+        assert_eq!(text(&vault.oriented(1, false)), ["##", ".+", "g#"]);
+        assert_eq!(text(&vault.oriented(2, false)), ["g.#", "#+#"]);
+        assert_eq!(text(&vault.oriented(4, false)), ["#+#", "#.g"]);
+        assert_eq!(text(&vault.oriented(0, true)), ["#+#", "g.#"]);
+        assert_eq!(text(&vault.oriented(1, true)), ["##", "+.", "#g"]);
+    }
+
+    #[test]
+    fn stamped_vaults_bring_their_tiles_monsters_and_items() {
+        let mut level = Level::new(10, 10, (0, 0));
+        let rows = vault("#=#\n#g!\n#*#").rows;
+        stamp(&mut level, &rows, (3, 4), &mut SmallRng::seed_from_u64(1));
+        assert!(level.get_tile(4, 4) == Tile::DoorLocked);
+        assert!(level.get_tile(3, 5) == Tile::Wall);
+        assert!(level.get_tile(4, 5) == Tile::Floor);
+        assert!(level.get_tile(5, 5) == Tile::Floor);
+        assert!(level.get_tile(4, 6) == Tile::Torch);
+        assert_eq!(level.actor_at(4, 5), Some(0));
+        assert!(level.item_at(5, 5).is_some());
+    }
+
+    #[test]
+    fn vaults_go_into_solid_rock_and_their_doors_are_joined() {
+        let mut doors = 0;
+        for seed in 0..20 {
+            let mut level = Level::new(60, 30, (0, 0));
+            let area = Area {
+                tiles: (5..15).flat_map(|x| (5..10).map(move |y| (x, y))).collect(),
+                kind: RoomKind::Chamber,
+            };
+            for &(x, y) in &area.tiles {
+                level.set_tile(x, y, Tile::Floor);
+            }
+            let mut rng = SmallRng::seed_from_u64(seed);
+            place_vaults(&mut level, std::slice::from_ref(&area), 2, &mut rng);
+
+            // nothing is built over the area, or right next to it
+            for &(x, y) in &area.tiles {
+                for (nx, ny) in
+                    (x - 1..=x + 1).flat_map(|nx| (y - 1..=y + 1).map(move |ny| (nx, ny)))
+                {
+                    assert!(
+                        level.room_at(nx, ny) != Some(RoomKind::Vault),
+                        "seed {seed}"
+                    );
+                }
+            }
+            // and each door leads to it
+            let distances = DijkstraMap::new(&level, &[area.tiles[0]]);
+            for y in 0..level.height() {
+                for x in 0..level.width() {
+                    if level.room_at(x, y) != Some(RoomKind::Vault)
+                        || !matches!(level.get_tile(x, y), Tile::DoorClosed | Tile::DoorLocked)
+                    {
+                        continue;
+                    }
+                    doors += 1;
+                    let joined = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                        .into_iter()
+                        .any(|(nx, ny)| {
+                            level.room_at(nx, ny) != Some(RoomKind::Vault)
+                                && distances.get(nx, ny).is_some()
+                        });
+                    assert!(joined, "seed {seed}: door at {x},{y}");
+                }
+            }
+        }
+        assert!(doors > 0);
+    }
+
+    #[test]
+    fn vaults_are_selected_once() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("vaults");
+        // other tests may have fixed the built-in vaults already, which
+        // are the same
+        let _ = select(&dir);
+        let e = select(&dir).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+    }
+}
@@ -0,0 +1,43 @@
+use rand::{Rng, rngs::SmallRng};
+
+use super::{Area, Generator, Level, Tile, sector_areas};
+
+/// Share of the map to dig out, in percent.
+const FLOOR_PERCENT: usize = 35;
+/// Steps a digger takes before another one sets off from dug ground.
+const WALK_LENGTH: usize = 200;
+
+/// Winding tunnels dug by diggers wandering at random from the middle of the map.
+pub struct DrunkardsWalk;
+
+impl Generator for DrunkardsWalk {
+    fn carve(
+        &self,
+        width: usize,
+        height: usize,
+        rng: &mut SmallRng,
+    ) -> Result<(Level, Vec<Area>), String> {
+        let entry_point = (width / 2, height / 2);
+        let mut level = Level::new(width, height, entry_point);
+        level.set_tile(entry_point.0, entry_point.1, Tile::Floor);
+        let mut dug = vec![entry_point];
+
+        let goal = (width - 2) * (height - 2) * FLOOR_PERCENT / 100;
+        while dug.len() < goal {
+            let (mut x, mut y) = dug[rng.random_range(0..dug.len())];
+            for _ in 0..WALK_LENGTH {
+                let (dx, dy) = [(-1, 0), (1, 0), (0, -1), (0, 1)][rng.random_range(0..4)];
+                // the edge of the map is left alone
+                x = x.saturating_add_signed(dx).clamp(1, width - 2);
+                y = y.saturating_add_signed(dy).clamp(1, height - 2);
+                if level.get_tile(x, y) == Tile::Wall {
+                    level.set_tile(x, y, Tile::Floor);
+                    dug.push((x, y));
+                }
+            }
+        }
+
+        let areas = sector_areas(&level, 32);
+        Ok((level, areas))
+    }
+}
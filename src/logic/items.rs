@@ -0,0 +1,97 @@
+use std::io;
+
+use super::save::{Persist, Reader, Writer, corrupt};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Item {
+    HealingPotion,
+    LightningScroll,
+    MappingScroll,
+    OilFlask,
+}
+
+impl Item {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::HealingPotion => "healing potion",
+            Item::LightningScroll => "scroll of lightning",
+            Item::MappingScroll => "scroll of magic mapping",
+            Item::OilFlask => "flask of oil",
+        }
+    }
+
+    /// Whether the item is used on a chosen position, with `Action::UseOn`.
+    pub fn needs_target(&self) -> bool {
+        matches!(self, Item::LightningScroll)
+    }
+}
+
+impl Persist for Item {
+    fn save(&self, w: &mut Writer) {
+        w.u8(match self {
+            Item::HealingPotion => 0,
+            Item::LightningScroll => 1,
+            Item::MappingScroll => 2,
+            Item::OilFlask => 3,
+        });
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        match r.u8()? {
+            0 => Ok(Item::HealingPotion),
+            1 => Ok(Item::LightningScroll),
+            2 => Ok(Item::MappingScroll),
+            3 => Ok(Item::OilFlask),
+            _ => Err(corrupt("unknown item")),
+        }
+    }
+}
+
+pub struct Inventory {
+    capacity: usize,
+    items: Vec<Item>,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+
+    pub fn add(&mut self, item: Item) {
+        debug_assert!(!self.is_full());
+        self.items.push(item);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Item {
+        self.items.remove(index)
+    }
+}
+
+impl Persist for Inventory {
+    fn save(&self, w: &mut Writer) {
+        w.usize(self.capacity);
+        w.seq(self.items.iter());
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let inventory = Inventory {
+            capacity: r.usize()?,
+            items: r.seq()?,
+        };
+        if inventory.items.len() > inventory.capacity {
+            return Err(corrupt("too many items"));
+        }
+        Ok(inventory)
+    }
+}
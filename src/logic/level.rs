@@ -1,6 +1,62 @@
-use super::fov::compute_fov;
+use std::io;
+
+use super::fov::Fov;
+use super::items::Item;
+use super::save::{Persist, Reader, Writer, corrupt};
 use super::{Entity, Tile};
 
+/// Light level from which a tile is fully lit; dimmer tiles are still visible.
+pub const FULL_LIGHT: u8 = 4;
+
+/// Top left and bottom right corners of a rectangle.
+type Rect = ((usize, usize), (usize, usize));
+
+/// What a part of a level was built for.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RoomKind {
+    Chamber,
+    Cave,
+    Corridor,
+    Courtyard,
+    GreatHall,
+    Barracks,
+    ThroneRoom,
+    Tower,
+    /// Drawn by hand rather than generated.
+    Vault,
+}
+
+impl Persist for RoomKind {
+    fn save(&self, w: &mut Writer) {
+        w.u8(match self {
+            RoomKind::Chamber => 0,
+            RoomKind::Cave => 1,
+            RoomKind::Corridor => 2,
+            RoomKind::Courtyard => 3,
+            RoomKind::GreatHall => 4,
+            RoomKind::Barracks => 5,
+            RoomKind::ThroneRoom => 6,
+            RoomKind::Tower => 7,
+            RoomKind::Vault => 8,
+        });
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        match r.u8()? {
+            0 => Ok(RoomKind::Chamber),
+            1 => Ok(RoomKind::Cave),
+            2 => Ok(RoomKind::Corridor),
+            3 => Ok(RoomKind::Courtyard),
+            4 => Ok(RoomKind::GreatHall),
+            5 => Ok(RoomKind::Barracks),
+            6 => Ok(RoomKind::ThroneRoom),
+            7 => Ok(RoomKind::Tower),
+            8 => Ok(RoomKind::Vault),
+            _ => Err(corrupt("unknown room kind")),
+        }
+    }
+}
+
 pub struct Level {
     width: usize,
     height: usize,
@@ -8,6 +64,11 @@ pub struct Level {
     tiles: Vec<Tile>,
     visible: Vec<bool>,
     explored: Vec<bool>,
+    /// How brightly each tile is lit, from 0 (dark) to [`FULL_LIGHT`].
+    light: Vec<u8>,
+    items: Vec<((usize, usize), Item)>,
+    /// Parts of the level; where they overlap, the first one counts.
+    rooms: Vec<(Rect, RoomKind)>,
     actors: Vec<Entity>,
     player: Option<Entity>,
 }
@@ -21,6 +82,9 @@ impl Level {
             tiles: vec![Tile::Wall; width * height],
             visible: vec![false; width * height],
             explored: vec![false; width * height],
+            light: vec![0; width * height],
+            items: Vec::new(),
+            rooms: Vec::new(),
             actors: Vec::new(),
             player: None,
         }
@@ -38,6 +102,12 @@ impl Level {
         self.entry
     }
 
+    /// Position of the stairs leading further down, if any.
+    pub fn exit(&self) -> Option<(usize, usize)> {
+        let index = self.tiles.iter().position(|&t| t == Tile::DownStairs)?;
+        Some((index % self.width, index / self.width))
+    }
+
     pub fn get_tile(&self, x: usize, y: usize) -> Tile {
         debug_assert!(x < self.width && y < self.height);
         self.tiles[y * self.width + x]
@@ -58,6 +128,63 @@ impl Level {
         self.explored[y * self.width + x]
     }
 
+    pub fn light(&self, x: usize, y: usize) -> u8 {
+        debug_assert!(x < self.width && y < self.height);
+        self.light[y * self.width + x]
+    }
+
+    /// Mark as explored every walkable tile and the walls around it.
+    pub fn reveal_map(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let near_floor = (y.saturating_sub(1)..=(y + 1).min(self.height - 1)).any(|ny| {
+                    (x.saturating_sub(1)..=(x + 1).min(self.width - 1))
+                        .any(|nx| self.get_tile(nx, ny).is_walkable())
+                });
+                if near_floor {
+                    self.explored[y * self.width + x] = true;
+                }
+            }
+        }
+    }
+
+    pub fn items(&self) -> &[((usize, usize), Item)] {
+        &self.items
+    }
+
+    pub fn item_at(&self, x: usize, y: usize) -> Option<Item> {
+        let (_, item) = self.items.iter().rev().find(|(pos, _)| *pos == (x, y))?;
+        Some(*item)
+    }
+
+    pub fn add_item(&mut self, x: usize, y: usize, item: Item) {
+        self.items.push(((x, y), item));
+    }
+
+    /// Remove the topmost item lying at the given position.
+    pub fn take_item(&mut self, x: usize, y: usize) -> Option<Item> {
+        let index = self.items.iter().rposition(|(pos, _)| *pos == (x, y))?;
+        Some(self.items.remove(index).1)
+    }
+
+    /// Record what the tiles from `top_left` to `bottom_right` were built for.
+    pub fn add_room(
+        &mut self,
+        top_left: (usize, usize),
+        bottom_right: (usize, usize),
+        kind: RoomKind,
+    ) {
+        self.rooms.push(((top_left, bottom_right), kind));
+    }
+
+    /// What the part of the level around a position was built for, if anything.
+    pub fn room_at(&self, x: usize, y: usize) -> Option<RoomKind> {
+        let (_, kind) = self.rooms.iter().find(|&&((from, to), _)| {
+            (from.0..=to.0).contains(&x) && (from.1..=to.1).contains(&y)
+        })?;
+        Some(*kind)
+    }
+
     pub fn actors(&self) -> &[Entity] {
         &self.actors
     }
@@ -66,6 +193,26 @@ impl Level {
         self.actors.push(e);
     }
 
+    pub fn remove_actor(&mut self, index: usize) -> Entity {
+        self.actors.remove(index)
+    }
+
+    pub fn actor_mut(&mut self, index: usize) -> &mut Entity {
+        &mut self.actors[index]
+    }
+
+    pub fn actor_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.actors.iter().position(|e| e.pos() == (x, y))
+    }
+
+    pub fn player_and_actor_mut(&mut self, index: usize) -> (&mut Entity, &mut Entity) {
+        (self.player.as_mut().unwrap(), &mut self.actors[index])
+    }
+
+    pub fn is_occupied(&self, x: usize, y: usize) -> bool {
+        self.actor_at(x, y).is_some() || self.player.as_ref().is_some_and(|p| p.pos() == (x, y))
+    }
+
     pub fn player(&self) -> Option<&Entity> {
         self.player.as_ref()
     }
@@ -78,19 +225,152 @@ impl Level {
         self.player = Some(e);
     }
 
-    pub fn update_vision(&mut self) {
-        // update visibility based on the player's position
-        compute_fov(
+    pub fn take_player(&mut self) -> Option<Entity> {
+        self.player.take()
+    }
+
+    /// The tiles in the player's line of sight within `radius`, lit or not.
+    ///
+    /// Shadowcasting is symmetric, so these are also the tiles from which the
+    /// player can be seen; with raycasting this is only close.
+    pub fn player_line_of_sight(&self, radius: usize, fov: Fov) -> Vec<bool> {
+        let (px, py) = self.player.as_ref().unwrap().pos();
+        let mut in_sight = vec![false; self.width * self.height];
+        fov.compute(
+            &mut in_sight,
+            self.width as i32,
+            self.height as i32,
+            |x, y| self.tiles[y as usize * self.width + x as usize].is_transparent(),
+            (px as i32, py as i32),
+            radius as i32,
+        );
+        in_sight
+    }
+
+    /// Update what the player sees: the tiles in their line of sight that
+    /// are lit, and the ones right next to them even in the dark.
+    pub fn update_vision(&mut self, fov: Fov) {
+        self.update_light(fov);
+        let player = self.player.as_ref().unwrap();
+        let (px, py) = player.pos();
+        fov.compute(
             &mut self.visible,
             self.width as i32,
             self.height as i32,
-            |x, y| self.tiles[y as usize * self.width + x as usize] != Tile::Wall,
-            self.player.as_ref().unwrap().x() as i32,
-            self.player.as_ref().unwrap().y() as i32,
+            |x, y| self.tiles[y as usize * self.width + x as usize].is_transparent(),
+            (px as i32, py as i32),
+            player.sight as i32,
         );
+        for (i, v) in self.visible.iter_mut().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            let adjacent = x.abs_diff(px) <= 1 && y.abs_diff(py) <= 1;
+            *v = *v && (self.light[i] > 0 || adjacent);
+        }
         // update explored tiles based on current visibility
         for (e, v) in self.explored.iter_mut().zip(&self.visible) {
             *e = *e || *v;
         }
     }
+
+    /// Add up the light shed by burning tiles and glowing entities.
+    ///
+    /// Each source lights the tiles it can see, less and less brightly up to
+    /// its radius.
+    fn update_light(&mut self, fov: Fov) {
+        self.light.fill(0);
+        let Some(player) = &self.player else {
+            return;
+        };
+        let mut sources: Vec<((usize, usize), usize)> = Vec::new();
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if tile.light() > 0 {
+                sources.push(((i % self.width, i / self.width), tile.light()));
+            }
+        }
+        let entities = self.actors.iter().chain(Some(player));
+        sources.extend(entities.filter(|e| e.light > 0).map(|e| (e.pos(), e.light)));
+        // lights too far away to shine on anything in the player's sight do not matter
+        let (px, py) = player.pos();
+        sources.retain(|&((x, y), radius)| {
+            let reach = player.sight + radius;
+            x.abs_diff(px).pow(2) + y.abs_diff(py).pow(2) <= reach * reach
+        });
+
+        let mut lit = vec![false; self.width * self.height];
+        for ((sx, sy), radius) in sources {
+            let origin = (sx as i32, sy as i32);
+            // a torch on a wall shines out of it
+            fov.compute(
+                &mut lit,
+                self.width as i32,
+                self.height as i32,
+                |x, y| {
+                    (x, y) == origin
+                        || self.tiles[y as usize * self.width + x as usize].is_transparent()
+                },
+                origin,
+                radius as i32,
+            );
+            for y in sy.saturating_sub(radius)..=(sy + radius).min(self.height - 1) {
+                for x in sx.saturating_sub(radius)..=(sx + radius).min(self.width - 1) {
+                    let i = y * self.width + x;
+                    let distance = (x.abs_diff(sx).pow(2) + y.abs_diff(sy).pow(2)).isqrt();
+                    if lit[i] && distance <= radius {
+                        let brightness = (radius + 1 - distance).min(FULL_LIGHT as usize) as u8;
+                        self.light[i] = (self.light[i] + brightness).min(FULL_LIGHT);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Persist for Level {
+    fn save(&self, w: &mut Writer) {
+        w.usize(self.width);
+        w.usize(self.height);
+        w.usize(self.entry.0);
+        w.usize(self.entry.1);
+        w.seq(self.tiles.iter());
+        w.seq(self.visible.iter());
+        w.seq(self.explored.iter());
+        w.seq(self.light.iter());
+        w.seq(self.items.iter());
+        w.seq(self.rooms.iter());
+        w.seq(self.actors.iter());
+        self.player.save(w);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let level = Level {
+            width: r.usize()?,
+            height: r.usize()?,
+            entry: (r.usize()?, r.usize()?),
+            tiles: r.seq()?,
+            visible: r.seq()?,
+            explored: r.seq()?,
+            light: r.seq()?,
+            items: r.seq()?,
+            rooms: r.seq()?,
+            actors: r.seq()?,
+            player: Option::load(r)?,
+        };
+        let size = level.width * level.height;
+        if level.tiles.len() != size
+            || level.visible.len() != size
+            || level.explored.len() != size
+            || level.light.len() != size
+        {
+            return Err(corrupt("map size mismatch"));
+        }
+        let in_bounds = |(x, y): (usize, usize)| x < level.width && y < level.height;
+        if !in_bounds(level.entry)
+            || !level.items.iter().all(|(pos, _)| in_bounds(*pos))
+            || !level.actors.iter().all(|e| in_bounds(e.pos()))
+            || !level.player.as_ref().is_none_or(|p| in_bounds(p.pos()))
+        {
+            return Err(corrupt("position out of bounds"));
+        }
+        Ok(level)
+    }
 }
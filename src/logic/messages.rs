@@ -1,4 +1,7 @@
 use std::collections::VecDeque;
+use std::io;
+
+use super::save::{Persist, Reader, Writer, corrupt};
 
 pub struct MessageLog {
     max_memory: usize,
@@ -26,6 +29,10 @@ impl MessageLog {
         self.curr_turn += 1;
     }
 
+    pub fn turn(&self) -> u64 {
+        self.curr_turn
+    }
+
     pub fn latest(&self, count: usize) -> impl Iterator<Item = (&str, u64)> {
         self.messages
             .iter()
@@ -46,4 +53,48 @@ impl MessageLog {
     pub fn len(&self) -> usize {
         self.messages.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl Persist for MessageLog {
+    fn save(&self, w: &mut Writer) {
+        w.usize(self.max_memory);
+        w.u64(self.curr_turn);
+        w.usize(self.messages.len());
+        for (msg, turn) in &self.messages {
+            w.str(msg);
+            w.u64(*turn);
+        }
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        let mut log = MessageLog {
+            max_memory: r.usize()?,
+            messages: VecDeque::new(),
+            curr_turn: r.u64()?,
+        };
+        for _ in 0..r.usize()? {
+            let msg = r.str()?;
+            let turn = r.u64()?;
+            if turn > log.curr_turn {
+                return Err(corrupt("message from the future"));
+            }
+            log.messages.push_back((msg, turn));
+        }
+        if log.messages.len() > log.max_memory {
+            return Err(corrupt("too many messages"));
+        }
+        Ok(log)
+    }
+}
+
+pub fn capitalize(msg: &str) -> String {
+    let mut chars = msg.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
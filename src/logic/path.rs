@@ -0,0 +1,219 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use super::Tile;
+use super::level::Level;
+
+/// Extra cost of stepping through a tile held by another actor, so that
+/// paths route around crowds but are not blocked by them.
+const OCCUPIED_COST: u32 = 10;
+
+/// A* search from `from` to `to` over the tiles a monster is willing to enter.
+///
+/// The returned path excludes the starting point and ends at the goal.
+pub fn find_path(
+    level: &Level,
+    from: (usize, usize),
+    to: (usize, usize),
+) -> Option<Vec<(usize, usize)>> {
+    search(level, from, to, |(x, y)| {
+        monster_can_enter(level.get_tile(x, y))
+    })
+}
+
+/// Like `find_path`, but for the player: only through tiles already
+/// explored, around anything that would hurt and not through locked doors,
+/// which are better forced on purpose.
+pub fn find_known_path(
+    level: &Level,
+    from: (usize, usize),
+    to: (usize, usize),
+) -> Option<Vec<(usize, usize)>> {
+    search(level, from, to, |(x, y)| {
+        let tile = level.get_tile(x, y);
+        level.is_explored(x, y) && tile != Tile::DoorLocked && tile.properties().damage <= 0
+    })
+}
+
+/// Monsters open doors but do not force them, and keep out of water and
+/// anything that would hurt.
+pub fn monster_can_enter(tile: Tile) -> bool {
+    let properties = tile.properties();
+    tile != Tile::DoorLocked && !properties.swimmable && properties.damage <= 0
+}
+
+fn search(
+    level: &Level,
+    from: (usize, usize),
+    to: (usize, usize),
+    allowed: impl Fn((usize, usize)) -> bool,
+) -> Option<Vec<(usize, usize)>> {
+    let width = level.width();
+    let index = |(x, y): (usize, usize)| y * width + x;
+
+    let mut cost = vec![u32::MAX; width * level.height()];
+    let mut came_from = vec![None; width * level.height()];
+    let mut open = BinaryHeap::new();
+    cost[index(from)] = 0;
+    open.push(Reverse((distance(from, to), from)));
+
+    while let Some(Reverse((_, pos))) = open.pop() {
+        if pos == to {
+            let mut path = Vec::new();
+            let mut curr = pos;
+            while curr != from {
+                path.push(curr);
+                curr = came_from[index(curr)].unwrap();
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for next in neighbors(level, pos).filter(|&next| allowed(next)) {
+            let step = if next != to && level.is_occupied(next.0, next.1) {
+                OCCUPIED_COST
+            } else {
+                1
+            };
+            let new_cost = cost[index(pos)] + step;
+            if new_cost < cost[index(next)] {
+                cost[index(next)] = new_cost;
+                came_from[index(next)] = Some(pos);
+                open.push(Reverse((new_cost + distance(next, to), next)));
+            }
+        }
+    }
+    None
+}
+
+/// The closest tile to `from` a monster can stand on, not held by any actor,
+/// `from` excluded.
+pub fn nearest_free(level: &Level, from: (usize, usize)) -> Option<(usize, usize)> {
+    let mut seen = vec![false; level.width() * level.height()];
+    let mut queue = VecDeque::from([from]);
+    seen[from.1 * level.width() + from.0] = true;
+    while let Some(pos) = queue.pop_front() {
+        let tile = level.get_tile(pos.0, pos.1);
+        if pos != from
+            && tile.is_walkable()
+            && monster_can_enter(tile)
+            && !level.is_occupied(pos.0, pos.1)
+        {
+            return Some(pos);
+        }
+        for next in neighbors(level, pos) {
+            if !seen[next.1 * level.width() + next.0] {
+                seen[next.1 * level.width() + next.0] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Distance field over walkable tiles and doors, also known as a Dijkstra map.
+pub struct DijkstraMap {
+    width: usize,
+    values: Vec<i32>,
+}
+
+impl DijkstraMap {
+    /// Map of the distance from every tile to the nearest goal.
+    pub fn new(level: &Level, goals: &[(usize, usize)]) -> Self {
+        Self::within(level, goals, i32::MAX)
+    }
+
+    /// Like `new`, but only out to `limit` steps from the goals: tiles that
+    /// far away or further have no value.
+    pub fn within(level: &Level, goals: &[(usize, usize)], limit: i32) -> Self {
+        let seeds = goals.iter().map(|&pos| (pos, 0)).collect();
+        Self::scan(level, seeds, limit)
+    }
+
+    /// Map that leads away from the goals of this map, preferring open areas
+    /// over dead ends.
+    pub fn flee(&self, level: &Level) -> Self {
+        let seeds = (0..self.values.len())
+            .filter(|&i| self.values[i] != i32::MAX)
+            .map(|i| ((i % self.width, i / self.width), -self.values[i] * 6 / 5))
+            .collect();
+        Self::scan(level, seeds, i32::MAX)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<i32> {
+        let value = self.values[y * self.width + x];
+        (value != i32::MAX).then_some(value)
+    }
+
+    /// The free neighbouring tile with the lowest value, if it improves on the
+    /// current one and a monster can enter it.
+    pub fn downhill(&self, level: &Level, from: (usize, usize)) -> Option<(usize, usize)> {
+        let here = self.get(from.0, from.1)?;
+        neighbors(level, from)
+            .filter(|&(x, y)| !level.is_occupied(x, y) && monster_can_enter(level.get_tile(x, y)))
+            .filter_map(|(x, y)| Some(((x, y), self.get(x, y)?)))
+            .filter(|&(_, value)| value < here)
+            .min_by_key(|&(_, value)| value)
+            .map(|(pos, _)| pos)
+    }
+
+    fn scan(level: &Level, seeds: Vec<((usize, usize), i32)>, limit: i32) -> Self {
+        let width = level.width();
+        let mut values = vec![i32::MAX; width * level.height()];
+        let mut open = BinaryHeap::new();
+        for (pos, value) in seeds {
+            values[pos.1 * width + pos.0] = value;
+            open.push(Reverse((value, pos)));
+        }
+        while let Some(Reverse((value, pos))) = open.pop() {
+            if value > values[pos.1 * width + pos.0] || value + 1 >= limit {
+                continue;
+            }
+            for next in neighbors(level, pos) {
+                if value + 1 < values[next.1 * width + next.0] {
+                    values[next.1 * width + next.0] = value + 1;
+                    open.push(Reverse((value + 1, next)));
+                }
+            }
+        }
+        DijkstraMap { width, values }
+    }
+}
+
+/// Chebyshev distance, the exact cost of an unobstructed 8-way walk.
+fn distance(a: (usize, usize), b: (usize, usize)) -> u32 {
+    a.0.abs_diff(b.0).max(a.1.abs_diff(b.1)) as u32
+}
+
+/// Adjacent tiles that can be walked onto, or opened on the way through.
+fn neighbors(level: &Level, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|&d| d != (0, 0))
+        .filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx)?;
+            let ny = y.checked_add_signed(dy)?;
+            let passable = |tile: Tile| tile.is_walkable() || tile.opened().is_some();
+            (nx < level.width() && ny < level.height() && passable(level.get_tile(nx, ny)))
+                .then_some((nx, ny))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::generate::generate_level;
+
+    #[test]
+    fn limited_map_agrees_with_full_map() {
+        let level = generate_level(7, 1).unwrap();
+        let entry = level.entry();
+        let full = DijkstraMap::new(&level, &[entry]);
+        let limited = DijkstraMap::within(&level, &[entry], 8);
+        for y in 0..level.height() {
+            for x in 0..level.width() {
+                let expected = full.get(x, y).filter(|&distance| distance < 8);
+                assert_eq!(limited.get(x, y), expected, "at {x}, {y}");
+            }
+        }
+    }
+}
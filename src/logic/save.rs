@@ -0,0 +1,286 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"GOBLINSV";
+const VERSION: u16 = 11;
+
+/// Binary encoding of a value into a save file.
+pub trait Persist: Sized {
+    fn save(&self, w: &mut Writer);
+    fn load(r: &mut Reader) -> Result<Self, io::Error>;
+}
+
+/// Write a save file in full next to `path`, then move it into place, so
+/// that a crash halfway leaves the previous file untouched.
+pub fn write_file<T: Persist>(path: &Path, value: &T) -> Result<(), io::Error> {
+    let mut w = Writer { bytes: Vec::new() };
+    w.bytes.extend_from_slice(MAGIC);
+    w.u16(VERSION);
+    value.save(&mut w);
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, w.bytes)?;
+    fs::rename(&temp, path)
+}
+
+pub fn read_file<T: Persist>(path: &Path) -> Result<T, io::Error> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(MAGIC) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "not a Goblin Castle save file",
+        ));
+    }
+    let mut r = Reader {
+        bytes: &bytes[MAGIC.len()..],
+    };
+    let version = r.u16()?;
+    if version != VERSION {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("incompatible save file version {version}"),
+        ));
+    }
+    let value = T::load(&mut r)?;
+    if !r.bytes.is_empty() {
+        return Err(corrupt("trailing data at end of save file"));
+    }
+    Ok(value)
+}
+
+pub fn corrupt(what: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("corrupt save file: {what}"))
+}
+
+pub struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.u32(value as u32);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn seq<'a, T: Persist + 'a>(&mut self, items: impl ExactSizeIterator<Item = &'a T>) {
+        self.usize(items.len());
+        for item in items {
+            item.save(self);
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], io::Error> {
+        if count > self.bytes.len() {
+            return Err(corrupt("unexpected end of file"));
+        }
+        let (head, tail) = self.bytes.split_at(count);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, io::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, io::Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, io::Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, io::Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, io::Error> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, io::Error> {
+        Ok(self.u32()? as usize)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, io::Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt("invalid boolean")),
+        }
+    }
+
+    pub fn str(&mut self) -> Result<String, io::Error> {
+        let len = self.usize()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt("invalid text"))
+    }
+
+    pub fn seq<T: Persist>(&mut self) -> Result<Vec<T>, io::Error> {
+        let len = self.usize()?;
+        // every item takes at least one byte, so this bounds the allocation
+        if len > self.bytes.len() {
+            return Err(corrupt("unexpected end of file"));
+        }
+        (0..len).map(|_| T::load(self)).collect()
+    }
+}
+
+impl Persist for bool {
+    fn save(&self, w: &mut Writer) {
+        w.bool(*self);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        r.bool()
+    }
+}
+
+impl Persist for u8 {
+    fn save(&self, w: &mut Writer) {
+        w.u8(*self);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        r.u8()
+    }
+}
+
+impl Persist for usize {
+    fn save(&self, w: &mut Writer) {
+        w.usize(*self);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        r.usize()
+    }
+}
+
+impl<A: Persist, B: Persist> Persist for (A, B) {
+    fn save(&self, w: &mut Writer) {
+        self.0.save(w);
+        self.1.save(w);
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        Ok((A::load(r)?, B::load(r)?))
+    }
+}
+
+impl<T: Persist> Persist for Option<T> {
+    fn save(&self, w: &mut Writer) {
+        w.bool(self.is_some());
+        if let Some(value) = self {
+            value.save(w);
+        }
+    }
+
+    fn load(r: &mut Reader) -> Result<Self, io::Error> {
+        if r.bool()? {
+            Ok(Some(T::load(r)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logic::Game;
+    use crate::testing::{played_game, temp_path};
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip.sav");
+        let game = played_game();
+        write_file(&path, &game).unwrap();
+        let loaded: Game = read_file(&path).unwrap();
+        assert_eq!(loaded.seed(), game.seed());
+        assert_eq!(loaded.turn(), game.turn());
+        assert!(loaded.level().player().unwrap().pos() == game.level().player().unwrap().pos());
+
+        // saving what was loaded gives back the very same file
+        let copy = temp_path("round-trip-copy.sav");
+        write_file(&copy, &loaded).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), fs::read(&copy).unwrap());
+    }
+
+    #[test]
+    fn no_temporary_file_left_behind() {
+        let path = temp_path("atomic.sav");
+        write_file(&path, &played_game()).unwrap();
+        assert!(path.exists());
+        assert!(!temp_path("atomic.sav.tmp").exists());
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let path = temp_path("truncated.sav");
+        write_file(&path, &played_game()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        for len in [0, 4, MAGIC.len() + 1, bytes.len() / 2, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            let e = read_file::<Game>(&path).err().unwrap();
+            assert_eq!(e.kind(), ErrorKind::InvalidData, "truncated to {len} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_file() {
+        let path = temp_path("corrupt.sav");
+        write_file(&path, &played_game()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] ^= 0xff;
+        let mut wrong_version = bytes.clone();
+        wrong_version[MAGIC.len()] ^= 0xff;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        for (what, corrupted) in [
+            ("magic", wrong_magic),
+            ("version", wrong_version),
+            ("trailing data", trailing),
+        ] {
+            fs::write(&path, corrupted).unwrap();
+            let e = read_file::<Game>(&path).err().unwrap();
+            assert_eq!(e.kind(), ErrorKind::InvalidData, "{what}");
+        }
+    }
+}
@@ -1,4 +1,81 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, process};
+
+use castle::Options;
+
 fn main() {
     println!("Goblin Castle");
-    castle::run().unwrap();
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{msg}");
+            process::exit(2);
+        }
+    };
+    if let Err(e) = castle::run(options) {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        replay_delay: Duration::from_millis(100),
+        ..Options::default()
+    };
+    if let Ok(value) = env::var("CASTLE_SEED") {
+        options.seed = Some(parse_seed(&value)?);
+    }
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        match name.as_str() {
+            "--step" => {
+                options.replay_step = true;
+                continue;
+            }
+            "--headless" => {
+                options.headless = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = match inline.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("missing value for {name}")),
+        };
+        match name.as_str() {
+            "--seed" => options.seed = Some(parse_seed(&value)?),
+            "--record" => options.record = Some(PathBuf::from(value)),
+            "--replay" => options.replay = Some(PathBuf::from(value)),
+            "--theme" => options.theme = Some(value),
+            "--keys" => options.keymap = Some(value),
+            "--vaults" => options.vaults = Some(PathBuf::from(value)),
+            "--delay" => {
+                let millis = value
+                    .parse()
+                    .map_err(|_| format!("invalid delay: {value}"))?;
+                options.replay_delay = Duration::from_millis(millis);
+            }
+            _ => return Err(format!("unknown argument: {name}")),
+        }
+    }
+    if options.headless && options.replay.is_none() {
+        return Err("--headless requires --replay".to_string());
+    }
+    if options.keymap.is_some() && options.replay.is_some() {
+        return Err("--keys cannot be used with --replay, which has its own key map".to_string());
+    }
+    if options.vaults.is_some() && (options.record.is_some() || options.replay.is_some()) {
+        return Err("--vaults cannot be used with --record or --replay".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_seed(text: &str) -> Result<u64, String> {
+    castle::parse_seed(text).ok_or_else(|| format!("invalid seed: {text}"))
 }
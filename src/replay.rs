@@ -0,0 +1,220 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::vec;
+
+use crate::console::{Event, Mouse};
+
+const HEADER: &str = "castle-replay 2";
+
+/// Write the seed, the key map and every input event to a file, as they happen.
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+    /// Start a recording; `keymap` is the key map in use, in the format of
+    /// key map files, so that key presses mean the same on playback.
+    pub fn create(path: &Path, seed: u64, keymap: &str) -> Result<Self, io::Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{HEADER}")?;
+        writeln!(file, "seed 0x{seed:016X}")?;
+        for line in keymap.lines() {
+            writeln!(file, "keymap {line}")?;
+        }
+        file.flush()?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, event: &Event) -> Result<(), io::Error> {
+        writeln!(self.file, "{}", encode(event))?;
+        // flush every event so that nothing is lost if the game crashes
+        self.file.flush()
+    }
+}
+
+/// Events read back from a recorded file.
+pub struct Replay {
+    seed: u64,
+    keymap: String,
+    events: vec::IntoIter<Event>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().enumerate().peekable();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((_, line)) if line.starts_with("castle-replay ") => {
+                return Err(invalid("replay file from another version of the game"));
+            }
+            _ => return Err(invalid("not a Goblin Castle replay file")),
+        }
+        let seed = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("seed "))
+            .and_then(crate::parse_seed)
+            .ok_or_else(|| invalid("missing seed in replay file"))?;
+        let mut keymap = String::new();
+        while let Some(line) = lines.next_if(|(_, line)| line.starts_with("keymap ")) {
+            keymap.push_str(&line.1["keymap ".len()..]);
+            keymap.push('\n');
+        }
+        let events = lines
+            .map(|(n, line)| {
+                decode(line).ok_or_else(|| invalid(&format!("bad event on line {}", n + 1)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            seed,
+            keymap,
+            events: events.into_iter(),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The key map of the recording, in the format of key map files.
+    pub fn keymap(&self) -> &str {
+        &self.keymap
+    }
+
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.next()
+    }
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, what.to_string())
+}
+
+fn encode(event: &Event) -> String {
+    match event {
+        Event::Abort => "abort".to_string(),
+        Event::Resize(width, height) => format!("resize {width} {height}"),
+        Event::KeyChar(ch) => format!("char {ch}"),
+        Event::KeySpecial(_) | Event::KeyModified(..) => {
+            format!("key {}", event.key_name().unwrap())
+        }
+        Event::Mouse(mouse, x, y) => format!("mouse {} {x} {y}", mouse.name()),
+    }
+}
+
+fn decode(line: &str) -> Option<Event> {
+    if line == "abort" {
+        return Some(Event::Abort);
+    }
+    if let Some(rest) = line.strip_prefix("resize ") {
+        let (width, height) = rest.split_once(' ')?;
+        return Some(Event::Resize(width.parse().ok()?, height.parse().ok()?));
+    }
+    if let Some(rest) = line.strip_prefix("mouse ") {
+        let mut parts = rest.split(' ');
+        let mouse = Mouse::from_name(parts.next()?)?;
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        return parts.next().is_none().then_some(Event::Mouse(mouse, x, y));
+    }
+    if let Some(rest) = line.strip_prefix("char ") {
+        let mut chars = rest.chars();
+        let ch = chars.next()?;
+        return chars.next().is_none().then_some(Event::KeyChar(ch));
+    }
+    Event::from_key_name(line.strip_prefix("key ")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{Key, Modifiers, Press};
+    use crate::testing::temp_path;
+
+    fn every_kind_of_event() -> Vec<Event> {
+        let keys = [
+            Key::Enter,
+            Key::Escape,
+            Key::Backspace,
+            Key::Left,
+            Key::Right,
+            Key::Up,
+            Key::Down,
+            Key::Home,
+            Key::End,
+            Key::PgUp,
+            Key::PgDn,
+        ];
+        let mice = [Mouse::Move, Mouse::Click, Mouse::WheelUp, Mouse::WheelDown];
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+        let alt_shift = Modifiers {
+            alt: true,
+            shift: true,
+            ..Modifiers::default()
+        };
+        let mut events = vec![
+            Event::Abort,
+            Event::Resize(120, 50),
+            Event::KeyChar('x'),
+            Event::KeyChar('X'),
+            Event::KeyChar(' '),
+            Event::KeyChar('+'),
+            Event::KeyModified(ctrl, Press::Char('x')),
+            Event::KeyModified(ctrl, Press::Char(' ')),
+            Event::KeyModified(alt_shift, Press::Special(Key::PgUp)),
+        ];
+        events.extend(keys.map(Event::KeySpecial));
+        events.extend(mice.map(|mouse| Event::Mouse(mouse, 3, 41)));
+        events
+    }
+
+    #[test]
+    fn events_round_trip() {
+        for event in every_kind_of_event() {
+            let line = encode(&event);
+            assert!(decode(&line) == Some(event), "{line}");
+        }
+    }
+
+    #[test]
+    fn recording_plays_back() {
+        let path = temp_path("round-trip.replay");
+        let events = every_kind_of_event();
+        let keymap = "move-n = k up\nlook = ;\n";
+        let mut recorder = Recorder::create(&path, 0xC0FFEE, keymap).unwrap();
+        for event in &events {
+            recorder.record(event).unwrap();
+        }
+        drop(recorder);
+
+        let mut replay = Replay::open(&path).unwrap();
+        assert_eq!(replay.seed(), 0xC0FFEE);
+        assert_eq!(replay.keymap(), keymap);
+        for event in events {
+            assert!(replay.next_event() == Some(event), "{}", encode(&event));
+        }
+        assert!(replay.next_event().is_none());
+    }
+
+    #[test]
+    fn unknown_key_names_are_rejected() {
+        for name in ["f13", "ctrl+", "hyper+x", "pageup", "xy"] {
+            assert!(Event::from_key_name(name).is_none(), "{name}");
+            assert!(decode(&format!("key {name}")).is_none(), "{name}");
+        }
+        for line in [
+            "",
+            "char",
+            "char xy",
+            "mouse poke 1 2",
+            "mouse click 1",
+            "resize 80",
+        ] {
+            assert!(decode(line).is_none(), "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::logic::{Action, Game};
+
+/// A file path of its own for each test, as they run in parallel.
+pub fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("castle-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+/// A game on a fixed seed, after a walk around the courtyard that ends in
+/// a fight with a goblin.
+pub fn played_game() -> Game {
+    let mut game = Game::new(3).unwrap();
+    for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+        for _ in 0..12 {
+            let _ = game.perform(Action::Move(dx, dy));
+        }
+    }
+    for _ in 0..6 {
+        let _ = game.perform(Action::Move(-1, 1));
+    }
+    game
+}
@@ -1,13 +1,17 @@
 use crate::console::{Console, Event};
 use crate::logic::Game;
 
+mod camera;
 pub mod input;
+pub mod keymap;
 pub mod render;
 pub mod scenes;
-mod theme;
+mod settings;
+pub mod theme;
 
-pub const CONSOLE_WIDTH: usize = 80;
-pub const CONSOLE_HEIGHT: usize = 43;
+/// Smallest console that can fit the whole user interface.
+pub const MIN_CONSOLE_WIDTH: usize = 80;
+pub const MIN_CONSOLE_HEIGHT: usize = 43;
 
 pub const MAP_OFFSET_X: usize = 0;
 pub const MAP_OFFSET_Y: usize = 4;
@@ -16,13 +20,25 @@ pub const LOG_OFFSET_X: usize = 0;
 pub const LOG_OFFSET_Y: usize = 0;
 pub const LOG_LINES: usize = 4;
 
+/// The status line is always at the bottom of the console.
+pub const STATUS_OFFSET_X: usize = 0;
+
 pub const POPUP_MARGIN_H: usize = 6;
 pub const POPUP_MARGIN_V: usize = 3;
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum Command {
     Move(i8, i8),
+    Descend,
+    Ascend,
+    PickUp,
+    Inventory,
+    Drop,
     History,
+    Help,
+    Look,
     Scroll(i8),
+    Select(usize),
 }
 
 pub enum Transition {
@@ -36,6 +52,8 @@ pub enum Transition {
     Push(Box<dyn Scene>),
     /// Return to the previous scene.
     Pop,
+    /// Leave the game, whatever scenes are on the stack.
+    Quit,
 }
 
 pub trait Scene {
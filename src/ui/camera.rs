@@ -0,0 +1,160 @@
+use crate::console::Console;
+use crate::logic::Level;
+
+use super::{MAP_OFFSET_X, MAP_OFFSET_Y};
+
+/// Maps level coordinates to the part of the console that shows the map.
+///
+/// Levels smaller than the viewport are centered in it; larger levels
+/// scroll to keep the focus point centered, without going past the edges.
+#[derive(Clone, Copy)]
+pub struct Camera {
+    level_width: usize,
+    level_height: usize,
+    view_x: usize,
+    view_y: usize,
+    view_width: usize,
+    view_height: usize,
+    // screen position = level position + shift
+    shift_x: isize,
+    shift_y: isize,
+}
+
+impl Camera {
+    pub fn new(console: &Console, level: &Level, focus: (usize, usize)) -> Self {
+        let view_width = console.width() - MAP_OFFSET_X;
+        // the last line is the status line
+        let view_height = console.height() - MAP_OFFSET_Y - 1;
+        Self {
+            level_width: level.width(),
+            level_height: level.height(),
+            view_x: MAP_OFFSET_X,
+            view_y: MAP_OFFSET_Y,
+            view_width,
+            view_height,
+            shift_x: MAP_OFFSET_X as isize + shift(view_width, level.width(), focus.0),
+            shift_y: MAP_OFFSET_Y as isize + shift(view_height, level.height(), focus.1),
+        }
+    }
+
+    /// Console position of a level position, if that position is on the
+    /// level and in view.
+    ///
+    /// The inverse of [`Camera::to_level`]: both are `None` for anything
+    /// off the level or out of the viewport.
+    pub fn to_screen(self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if !self.on_level(x, y) {
+            return None;
+        }
+        let sx = usize::try_from(x as isize + self.shift_x).ok()?;
+        let sy = usize::try_from(y as isize + self.shift_y).ok()?;
+        self.in_view(sx, sy).then_some((sx, sy))
+    }
+
+    /// Level position shown at a console position, if the position is in
+    /// view and shows part of the level.
+    pub fn to_level(self, sx: usize, sy: usize) -> Option<(usize, usize)> {
+        if !self.in_view(sx, sy) {
+            return None;
+        }
+        let x = usize::try_from(sx as isize - self.shift_x).ok()?;
+        let y = usize::try_from(sy as isize - self.shift_y).ok()?;
+        self.on_level(x, y).then_some((x, y))
+    }
+
+    fn on_level(self, x: usize, y: usize) -> bool {
+        x < self.level_width && y < self.level_height
+    }
+
+    fn in_view(self, sx: usize, sy: usize) -> bool {
+        (self.view_x..self.view_x + self.view_width).contains(&sx)
+            && (self.view_y..self.view_y + self.view_height).contains(&sy)
+    }
+}
+
+/// Offset along one axis from level to viewport coordinates.
+fn shift(view: usize, level: usize, focus: usize) -> isize {
+    if level <= view {
+        ((view - level) / 2) as isize
+    } else {
+        let origin = focus.saturating_sub(view / 2).min(level - view);
+        -(origin as isize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::Memory;
+    use crate::ui::{MIN_CONSOLE_HEIGHT, MIN_CONSOLE_WIDTH};
+
+    /// Size of the viewport on the smallest console.
+    const VIEW: (usize, usize) = (
+        MIN_CONSOLE_WIDTH - MAP_OFFSET_X,
+        MIN_CONSOLE_HEIGHT - MAP_OFFSET_Y - 1,
+    );
+
+    fn camera(level: (usize, usize), focus: (usize, usize)) -> Camera {
+        let backend = Memory::new(MIN_CONSOLE_WIDTH, MIN_CONSOLE_HEIGHT, []);
+        let console = Console::with_backend(Box::new(backend), "test").unwrap();
+        Camera::new(&console, &Level::new(level.0, level.1, focus), focus)
+    }
+
+    #[test]
+    fn focus_is_centered() {
+        let camera = camera((200, 100), (100, 50));
+        let center = (MAP_OFFSET_X + VIEW.0 / 2, MAP_OFFSET_Y + VIEW.1 / 2);
+        assert_eq!(camera.to_screen(100, 50), Some(center));
+        assert_eq!(camera.to_level(center.0, center.1), Some((100, 50)));
+    }
+
+    #[test]
+    fn view_stops_at_level_edges() {
+        let top_left = camera((200, 100), (1, 1));
+        assert_eq!(top_left.to_screen(0, 0), Some((MAP_OFFSET_X, MAP_OFFSET_Y)));
+        let bottom_right = camera((200, 100), (198, 98));
+        let corner = (MAP_OFFSET_X + VIEW.0 - 1, MAP_OFFSET_Y + VIEW.1 - 1);
+        assert_eq!(bottom_right.to_screen(199, 99), Some(corner));
+        assert_eq!(bottom_right.to_screen(0, 0), None);
+    }
+
+    #[test]
+    fn small_level_is_centered_in_view() {
+        let camera = camera((20, 10), (5, 5));
+        let origin = (
+            MAP_OFFSET_X + (VIEW.0 - 20) / 2,
+            MAP_OFFSET_Y + (VIEW.1 - 10) / 2,
+        );
+        assert_eq!(camera.to_screen(0, 0), Some(origin));
+        // around the level the view is empty, whichever way it is looked at
+        assert_eq!(camera.to_screen(20, 0), None);
+        assert_eq!(camera.to_screen(0, 10), None);
+        assert_eq!(camera.to_level(origin.0 - 1, origin.1), None);
+        assert_eq!(camera.to_level(origin.0 + 20, origin.1), None);
+    }
+
+    #[test]
+    fn screen_and_level_positions_agree() {
+        for (level, focus) in [
+            ((20, 10), (5, 5)),
+            ((200, 100), (3, 97)),
+            ((200, 100), (120, 40)),
+        ] {
+            let camera = camera(level, focus);
+            for y in 0..level.1 + 2 {
+                for x in 0..level.0 + 2 {
+                    if let Some((sx, sy)) = camera.to_screen(x, y) {
+                        assert_eq!(camera.to_level(sx, sy), Some((x, y)));
+                    }
+                }
+            }
+            for sy in 0..MIN_CONSOLE_HEIGHT {
+                for sx in 0..MIN_CONSOLE_WIDTH {
+                    if let Some((x, y)) = camera.to_level(sx, sy) {
+                        assert_eq!(camera.to_screen(x, y), Some((sx, sy)));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,30 +1,10 @@
-use crate::console::{Event, Key};
+use crate::console::{Event, Key, Mouse};
 
-use super::Command;
+use super::{Command, keymap};
 
+/// Commands of the play screen follow the selected key map.
 pub fn map_play_command(event: Event) -> Option<Command> {
-    let command = match event {
-        Event::KeyChar('y') => Command::Move(-1, -1),
-        Event::KeyChar('k') => Command::Move(0, -1),
-        Event::KeyChar('u') => Command::Move(1, -1),
-        Event::KeyChar('h') => Command::Move(-1, 0),
-        Event::KeyChar('l') => Command::Move(1, 0),
-        Event::KeyChar('b') => Command::Move(-1, 1),
-        Event::KeyChar('j') => Command::Move(0, 1),
-        Event::KeyChar('n') => Command::Move(1, 1),
-        Event::KeySpecial(Key::Home) => Command::Move(-1, -1),
-        Event::KeySpecial(Key::Up) => Command::Move(0, -1),
-        Event::KeySpecial(Key::PgUp) => Command::Move(1, -1),
-        Event::KeySpecial(Key::Left) => Command::Move(-1, 0),
-        Event::KeySpecial(Key::Right) => Command::Move(1, 0),
-        Event::KeySpecial(Key::End) => Command::Move(-1, 1),
-        Event::KeySpecial(Key::Down) => Command::Move(0, 1),
-        Event::KeySpecial(Key::PgDn) => Command::Move(1, 1),
-        Event::KeyChar('.') => Command::Move(0, 0),
-        Event::KeyChar('m') => Command::History,
-        _ => return None,
-    };
-    Some(command)
+    keymap::command(event)
 }
 
 pub fn map_scroll_command(event: Event) -> Option<Command> {
@@ -35,7 +15,21 @@ pub fn map_scroll_command(event: Event) -> Option<Command> {
         Event::KeySpecial(Key::Down) => Command::Scroll(1),
         Event::KeySpecial(Key::PgDn) => Command::Scroll(10),
         Event::KeySpecial(Key::End) => Command::Scroll(i8::MAX),
+        Event::Mouse(Mouse::WheelUp, ..) => Command::Scroll(-3),
+        Event::Mouse(Mouse::WheelDown, ..) => Command::Scroll(3),
         _ => return None,
     };
     Some(command)
 }
+
+pub fn map_select_command(event: Event) -> Option<Command> {
+    match event {
+        Event::KeyChar(ch @ 'a'..='z') => Some(Command::Select(ch as usize - 'a' as usize)),
+        _ => None,
+    }
+}
+
+/// Mouse events that do not count as a key press: pointer motion and scrolling.
+pub fn is_passive_mouse(event: &Event) -> bool {
+    matches!(event, Event::Mouse(mouse, ..) if *mouse != Mouse::Click)
+}
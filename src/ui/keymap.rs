@@ -0,0 +1,236 @@
+use std::io;
+use std::sync::OnceLock;
+
+use crate::console::Event;
+
+use super::Command;
+use super::settings::{self, Settings};
+
+/// Key maps that do not need a file, by name.
+const PRESETS: [(&str, &str); 3] = [
+    ("vi", include_str!("../../keymaps/vi.keys")),
+    ("numpad", include_str!("../../keymaps/numpad.keys")),
+    ("wasd", include_str!("../../keymaps/wasd.keys")),
+];
+
+/// Commands that can be bound to keys, with their names in key map files.
+const COMMANDS: [(&str, Command, &str); 17] = [
+    ("move-nw", Command::Move(-1, -1), "Move north-west"),
+    ("move-n", Command::Move(0, -1), "Move north"),
+    ("move-ne", Command::Move(1, -1), "Move north-east"),
+    ("move-w", Command::Move(-1, 0), "Move west"),
+    ("move-e", Command::Move(1, 0), "Move east"),
+    ("move-sw", Command::Move(-1, 1), "Move south-west"),
+    ("move-s", Command::Move(0, 1), "Move south"),
+    ("move-se", Command::Move(1, 1), "Move south-east"),
+    ("wait", Command::Move(0, 0), "Wait a turn"),
+    ("descend", Command::Descend, "Go down the stairs"),
+    ("ascend", Command::Ascend, "Go up the stairs"),
+    ("pick-up", Command::PickUp, "Pick up an item"),
+    ("inventory", Command::Inventory, "Use an item"),
+    ("drop", Command::Drop, "Drop an item"),
+    ("look", Command::Look, "Look around"),
+    ("history", Command::History, "Show the message history"),
+    ("help", Command::Help, "Show this help"),
+];
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+/// Key bindings of the commands available while playing.
+struct Keymap {
+    bindings: Vec<(Event, Command)>,
+}
+
+impl Keymap {
+    fn vi() -> Self {
+        Self::preset("vi").unwrap().unwrap()
+    }
+
+    /// Names of the keys bound to a command, separated by spaces.
+    fn keys(&self, command: Command) -> String {
+        let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| *bound == command)
+            .filter_map(|(event, _)| event.key_name())
+            .collect();
+        keys.join(" ")
+    }
+
+    /// Every binding, in the format of key map files.
+    fn to_text(&self) -> String {
+        COMMANDS
+            .iter()
+            .map(|&(name, command, _)| format!("{name} = {}\n", self.keys(command)))
+            .collect()
+    }
+}
+
+impl Settings for Keymap {
+    const KIND: &'static str = "key map";
+    const PRESETS: &'static [(&'static str, &'static str)] = &PRESETS;
+
+    fn blank() -> Self {
+        Keymap { bindings: vec![] }
+    }
+
+    /// Bind the keys on a `command = key...` line.
+    ///
+    /// Each line replaces all the keys of its command, and a key can only be
+    /// bound to one command.
+    fn set(&mut self, name: &str, keys: &str) -> Result<(), String> {
+        let Some(&(_, command, _)) = COMMANDS.iter().find(|(command, ..)| *command == name) else {
+            return Err(format!("unknown command '{name}'"));
+        };
+        let mut events: Vec<Event> = Vec::new();
+        for key in keys.split_whitespace() {
+            let event = Event::from_key_name(key).ok_or_else(|| format!("unknown key '{key}'"))?;
+            // `K` and `shift+k` are the same key
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        self.bindings
+            .retain(|(event, bound)| *bound != command && !events.contains(event));
+        self.bindings
+            .extend(events.into_iter().map(|event| (event, command)));
+        Ok(())
+    }
+}
+
+/// Use a built-in key map or load one from a file, before any key is read.
+pub fn select(name_or_path: &str) -> Result<(), io::Error> {
+    settings::select(&KEYMAP, name_or_path, Keymap::vi)
+}
+
+/// Use a key map written out by `to_text`, as kept in recordings.
+pub fn select_recorded(text: &str) -> Result<(), io::Error> {
+    let keymap = Keymap::parse(text, Keymap::blank()).map_err(|e| {
+        let msg = format!("invalid recorded key map: {e}");
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    })?;
+    settings::install(&KEYMAP, keymap, "recorded in the replay")
+}
+
+/// The key map in use, in full and in the format of key map files.
+pub fn to_text() -> String {
+    current().to_text()
+}
+
+fn current() -> &'static Keymap {
+    KEYMAP.get_or_init(Keymap::vi)
+}
+
+/// The command bound to a key press, if any.
+pub fn command(event: Event) -> Option<Command> {
+    let bindings = &current().bindings;
+    bindings
+        .iter()
+        .find(|(bound, _)| *bound == event)
+        .map(|&(_, command)| command)
+}
+
+/// Description and key names of every command, in a fixed order.
+pub fn describe() -> Vec<(&'static str, String)> {
+    let keymap = current();
+    COMMANDS
+        .iter()
+        .map(|&(_, command, description)| (description, keymap.keys(command)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{Key, Modifiers, Press};
+
+    fn parse(text: &str) -> Result<Keymap, String> {
+        Keymap::parse(text, Keymap::blank())
+    }
+
+    fn keys(keymap: &Keymap, name: &str) -> String {
+        let &(_, command, _) = COMMANDS.iter().find(|(n, ..)| *n == name).unwrap();
+        keymap.keys(command)
+    }
+
+    fn bound(keymap: &Keymap, event: Event) -> Option<Command> {
+        let binding = keymap.bindings.iter().find(|(bound, _)| *bound == event);
+        binding.map(|&(_, command)| command)
+    }
+
+    #[test]
+    fn modifiers_are_parsed() {
+        let keymap = parse("look = ctrl+l alt+shift+up shift+x ctrl+shift+y space").unwrap();
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+        let alt_shift = Modifiers {
+            alt: true,
+            shift: true,
+            ..Modifiers::default()
+        };
+        let events = [
+            Event::KeyModified(ctrl, Press::Char('l')),
+            Event::KeyModified(alt_shift, Press::Special(Key::Up)),
+            // characters come shifted already
+            Event::KeyChar('x'),
+            Event::KeyModified(ctrl, Press::Char('y')),
+            Event::KeyChar(' '),
+        ];
+        for event in events {
+            assert!(
+                bound(&keymap, event) == Some(Command::Look),
+                "{:?}",
+                event.key_name()
+            );
+        }
+        assert!(bound(&keymap, Event::KeyChar('l')).is_none());
+        assert!(bound(&keymap, Event::KeySpecial(Key::Up)).is_none());
+    }
+
+    #[test]
+    fn unknown_modifiers_are_rejected() {
+        for value in ["hyper+k", "ctrl+", "ctrl+alt", "shift+f13"] {
+            let e = parse(&format!("look = {value}")).err().unwrap();
+            assert_eq!(e, format!("line 1: unknown key '{value}'"));
+        }
+    }
+
+    #[test]
+    fn lines_replace_keys() {
+        let keymap = parse("move-n = k up\nmove-n = w\nlook = k").unwrap();
+        assert_eq!(keys(&keymap, "move-n"), "w");
+        assert_eq!(keys(&keymap, "look"), "k");
+    }
+
+    #[test]
+    fn conflicting_bindings_go_to_the_last_command() {
+        // a key taken by another command is no longer bound to the first one
+        let keymap = parse("move-n = k up\nlook = k").unwrap();
+        assert_eq!(keys(&keymap, "move-n"), "up");
+        assert!(bound(&keymap, Event::KeyChar('k')) == Some(Command::Look));
+        // the same key twice on a line is bound once
+        let keymap = parse("look = ctrl+l ctrl+shift+l ; ;").unwrap();
+        assert_eq!(keys(&keymap, "look"), "ctrl+l ;");
+    }
+
+    #[test]
+    fn written_out_and_read_back() {
+        for (name, _) in PRESETS {
+            let keymap = Keymap::preset(name).unwrap().unwrap();
+            let copy = parse(&keymap.to_text()).unwrap();
+            for (command, ..) in COMMANDS {
+                assert_eq!(keys(&copy, command), keys(&keymap, command), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        assert_eq!(
+            parse("move-n = k\nfly = f").err().unwrap(),
+            "line 2: unknown command 'fly'"
+        );
+    }
+}
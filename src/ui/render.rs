@@ -1,45 +1,98 @@
 use crate::{console::Console, logic::Game};
 
+use super::camera::Camera;
 use super::theme::{self, Decoration};
 use super::{
-    CONSOLE_HEIGHT, CONSOLE_WIDTH, LOG_LINES, LOG_OFFSET_X, LOG_OFFSET_Y, MAP_OFFSET_X,
-    MAP_OFFSET_Y, POPUP_MARGIN_H, POPUP_MARGIN_V,
+    LOG_LINES, LOG_OFFSET_X, LOG_OFFSET_Y, POPUP_MARGIN_H, POPUP_MARGIN_V, STATUS_OFFSET_X,
 };
 
 pub fn render_map(console: &mut Console, game: &Game) {
-    for y in 0..game.level().height() {
-        for x in 0..game.level().width() {
-            if game.level().is_visible(x, y) {
-                console.set_cell(
-                    x + MAP_OFFSET_X,
-                    y + MAP_OFFSET_Y,
-                    theme::visible_tile(&game.level().get_tile(x, y)),
-                );
-            } else if game.level().is_explored(x, y) {
-                console.set_cell(
-                    x + MAP_OFFSET_X,
-                    y + MAP_OFFSET_Y,
-                    theme::explored_tile(&game.level().get_tile(x, y)),
-                );
+    let level = game.level();
+    let player = level.player().unwrap();
+    let camera = Camera::new(console, level, player.pos());
+    for y in 0..level.height() {
+        for x in 0..level.width() {
+            let Some((sx, sy)) = camera.to_screen(x, y) else {
+                continue;
+            };
+            if level.is_visible(x, y) {
+                let cell = theme::lit_tile(&level.get_tile(x, y), level.light(x, y));
+                console.set_cell(sx, sy, cell);
+            } else if level.is_explored(x, y) {
+                console.set_cell(sx, sy, theme::explored_tile(&level.get_tile(x, y)));
             }
         }
     }
-    for e in game.level().actors() {
-        if game.level().is_visible(e.x(), e.y()) {
-            console.set_cell(
-                e.x() + MAP_OFFSET_X,
-                e.y() + MAP_OFFSET_Y,
-                theme::glyph(&e.glyph),
-            );
+    for ((x, y), item) in level.items() {
+        if level.is_visible(*x, *y)
+            && let Some((sx, sy)) = camera.to_screen(*x, *y)
+        {
+            console.set_cell(sx, sy, theme::item(item));
         }
     }
-    let player = game.level().player().unwrap();
-    console.set_cell(
-        player.x() + MAP_OFFSET_X,
-        player.y() + MAP_OFFSET_Y,
-        theme::glyph(&player.glyph),
-    );
-    console.show_cursor(player.x() + MAP_OFFSET_X, player.y() + MAP_OFFSET_Y);
+    for e in level.actors() {
+        if level.is_visible(e.x(), e.y())
+            && let Some((sx, sy)) = camera.to_screen(e.x(), e.y())
+        {
+            console.set_cell(sx, sy, theme::glyph(&e.glyph));
+        }
+    }
+    // the camera is centered on the player, who is always in view
+    let (sx, sy) = camera.to_screen(player.x(), player.y()).unwrap();
+    console.set_cell(sx, sy, theme::glyph(&player.glyph));
+    console.show_cursor(sx, sy);
+}
+
+/// What the player knows about a level position, if they have seen it.
+pub fn describe(game: &Game, pos: (usize, usize)) -> Option<String> {
+    let level = game.level();
+    let (x, y) = pos;
+    if !level.is_explored(x, y) {
+        return None;
+    }
+    let mut parts = vec![];
+    // remembered tiles may have changed since, only show what is there for sure
+    if level.is_visible(x, y) {
+        let player = level.player().unwrap();
+        let actor = match level.actor_at(x, y) {
+            Some(index) => Some(&level.actors()[index]),
+            None => (player.pos() == pos).then_some(player),
+        };
+        if let Some(actor) = actor {
+            let fighter = &actor.fighter;
+            let mut state = vec![];
+            if fighter.hp() < fighter.max_hp() {
+                state.push("wounded");
+            }
+            if actor.ai.is_some_and(|ai| ai.is_asleep()) {
+                state.push("asleep");
+            }
+            if state.is_empty() {
+                parts.push(actor.glyph.name().to_string());
+            } else {
+                parts.push(format!("{} ({})", actor.glyph.name(), state.join(", ")));
+            }
+        }
+        if let Some(item) = level.item_at(x, y) {
+            parts.push(format!("a {}", item.name()));
+        }
+    }
+    parts.push(level.get_tile(x, y).name().to_string());
+    Some(parts.join(", "))
+}
+
+/// Show a short text next to the mouse pointer.
+pub fn render_tooltip(console: &mut Console, text: &str, at: (usize, usize)) {
+    let text = format!(" {text} ");
+    // beside the pointer, on the left if there is no room on the right
+    let (sx, sy) = at;
+    let width = text.chars().count();
+    let tx = if sx + 2 + width <= console.width() {
+        sx + 2
+    } else {
+        sx.saturating_sub(width + 1)
+    };
+    console.print(tx, sy, &text, theme::tooltip_fg(), theme::tooltip_bg());
 }
 
 pub fn render_log(console: &mut Console, game: &Game) {
@@ -54,11 +107,56 @@ pub fn render_log(console: &mut Console, game: &Game) {
     }
 }
 
+pub fn render_status(console: &mut Console, game: &Game) {
+    let fighter = &game.level().player().unwrap().fighter;
+    let status = format!(
+        "HP: {}/{}  Depth: {}  Oil: {}",
+        fighter.hp(),
+        fighter.max_hp(),
+        game.depth(),
+        game.lantern()
+    );
+    console.print(
+        STATUS_OFFSET_X,
+        console.height() - 1,
+        &status,
+        theme::status_fg(fighter.hp(), fighter.max_hp()),
+        theme::status_bg(),
+    );
+    let seed = format!("Seed: 0x{:016X}", game.seed());
+    console.print(
+        console.width() - seed.len(),
+        console.height() - 1,
+        &seed,
+        theme::seed_fg(),
+        theme::status_bg(),
+    );
+}
+
+/// Replace the status line with a prompt.
+pub fn render_prompt(console: &mut Console, text: &str) {
+    let y = console.height() - 1;
+    console.clear_rect(0, y, console.width(), 1);
+    let text: String = text.chars().take(console.width()).collect();
+    console.print(
+        STATUS_OFFSET_X,
+        y,
+        &text,
+        theme::prompt_fg(),
+        theme::status_bg(),
+    );
+}
+
+/// Number of text lines that fit inside a popup box.
+pub fn popup_lines(console: &Console) -> usize {
+    console.height() - POPUP_MARGIN_V * 2 - 2
+}
+
 pub fn render_history_box(console: &mut Console, game: &Game, scroll: usize) {
     let x0 = POPUP_MARGIN_H;
     let y0 = POPUP_MARGIN_V;
-    let x1 = CONSOLE_WIDTH - POPUP_MARGIN_H - 1;
-    let y1 = CONSOLE_HEIGHT - POPUP_MARGIN_V - 1;
+    let x1 = console.width() - POPUP_MARGIN_H - 1;
+    let y1 = console.height() - POPUP_MARGIN_V - 1;
     draw_box(console, x0, y0, x1, y1);
 
     let title = " Message history ";
@@ -68,8 +166,8 @@ pub fn render_history_box(console: &mut Console, game: &Game, scroll: usize) {
 
     let x0 = POPUP_MARGIN_H + 1;
     let y0 = POPUP_MARGIN_V + 1;
-    let width = CONSOLE_WIDTH - POPUP_MARGIN_H * 2 - 2;
-    let height = CONSOLE_HEIGHT - POPUP_MARGIN_V * 2 - 2;
+    let width = console.width() - POPUP_MARGIN_H * 2 - 2;
+    let height = popup_lines(console);
     console.clear_rect(x0, y0, width, height);
     for (n, msg) in game.log().peek(scroll, height).enumerate() {
         let line: String = msg.chars().take(width - 2).collect();
@@ -83,6 +181,75 @@ pub fn render_history_box(console: &mut Console, game: &Game, scroll: usize) {
     }
 }
 
+pub fn render_inventory_box(console: &mut Console, game: &Game, title: &str) {
+    let x0 = POPUP_MARGIN_H;
+    let y0 = POPUP_MARGIN_V;
+    let x1 = console.width() - POPUP_MARGIN_H - 1;
+    let y1 = console.height() - POPUP_MARGIN_V - 1;
+    draw_box(console, x0, y0, x1, y1);
+
+    draw_bracketed_center(console, (x0 + x1) / 2, y0, title);
+    let footer = " a-z ";
+    draw_bracketed_right(console, x1 - 2, y1, footer);
+
+    let x0 = POPUP_MARGIN_H + 1;
+    let y0 = POPUP_MARGIN_V + 1;
+    let width = console.width() - POPUP_MARGIN_H * 2 - 2;
+    let height = popup_lines(console);
+    console.clear_rect(x0, y0, width, height);
+    let items = game.inventory().items();
+    if items.is_empty() {
+        let line = "Your pack is empty.";
+        console.print(x0 + 1, y0, line, theme::history_fg(), theme::history_bg());
+    }
+    for (n, item) in items.iter().enumerate().take(height) {
+        let letter = (b'a' + n as u8) as char;
+        let line = format!("{letter}) {}", item.name());
+        console.print(
+            x0 + 1,
+            y0 + n,
+            &line,
+            theme::history_fg(),
+            theme::history_bg(),
+        );
+    }
+}
+
+pub fn render_help_box(console: &mut Console, commands: &[(&str, String)]) {
+    let x0 = POPUP_MARGIN_H;
+    let y0 = POPUP_MARGIN_V;
+    let x1 = console.width() - POPUP_MARGIN_H - 1;
+    let y1 = console.height() - POPUP_MARGIN_V - 1;
+    draw_box(console, x0, y0, x1, y1);
+
+    let title = " Commands ";
+    draw_bracketed_center(console, (x0 + x1) / 2, y0, title);
+
+    let x0 = POPUP_MARGIN_H + 1;
+    let y0 = POPUP_MARGIN_V + 1;
+    let width = console.width() - POPUP_MARGIN_H * 2 - 2;
+    let height = popup_lines(console);
+    console.clear_rect(x0, y0, width, height);
+    let column = commands
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    for (n, (name, keys)) in commands.iter().enumerate().take(height) {
+        let keys = if keys.is_empty() { "(none)" } else { keys };
+        let line = format!("{name:column$}{keys}");
+        let line: String = line.chars().take(width - 2).collect();
+        console.print(
+            x0 + 1,
+            y0 + n,
+            &line,
+            theme::history_fg(),
+            theme::history_bg(),
+        );
+    }
+}
+
 fn draw_box(console: &mut Console, x0: usize, y0: usize, x1: usize, y1: usize) {
     console.set_cell(x0, y0, theme::box_decoration(Decoration::TopLeftCorner));
     console.set_cell(x1, y0, theme::box_decoration(Decoration::TopRightCorner));
@@ -112,3 +279,132 @@ fn draw_bracketed_right(console: &mut Console, xr: usize, y: usize, text: &str)
     console.print(x0, y, text, theme::box_fg(), theme::box_bg());
     console.set_cell(xr, y, theme::box_decoration(Decoration::RightBracket));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{Buffer, Cell, Memory};
+    use crate::logic::Glyph;
+    use crate::testing::played_game;
+    use crate::ui::{MIN_CONSOLE_HEIGHT, MIN_CONSOLE_WIDTH};
+
+    /// The frame drawn by `render` on a blank console, and its cursor.
+    fn frame(render: impl FnOnce(&mut Console)) -> (Buffer, Option<(usize, usize)>) {
+        let backend = Memory::new(MIN_CONSOLE_WIDTH, MIN_CONSOLE_HEIGHT, []);
+        let mut console = Console::with_backend(Box::new(backend), "test").unwrap();
+        console.clear();
+        render(&mut console);
+        console.display().unwrap();
+        let (frame, cursor) = console.snapshot().unwrap();
+        (frame.clone(), cursor)
+    }
+
+    /// The text of a frame, without the blank lines around it.
+    fn text(frame: &Buffer) -> String {
+        frame.lines().join("\n").trim_matches('\n').to_string()
+    }
+
+    const MAP: &str = "
+             #######################
+            .........................
+           ..........................
+          #..........................
+          #..........................
+          #..........................
+          #.g.@......................
+          #.............. ...........
+          ...........................
+          ...........................
+           ..........................
+              .......................
+              .......................
+              .......................
+               .....................
+               .....................
+               .....................
+                ###################
+";
+
+    #[test]
+    fn map() {
+        let game = played_game();
+        let (frame, cursor) = frame(|console| render_map(console, &game));
+        assert_eq!(text(&frame), MAP.trim_matches('\n'));
+
+        // the cursor rests on the player, in their own colors
+        let (sx, sy) = cursor.unwrap();
+        assert!(frame.get(sx, sy) == theme::glyph(&Glyph::Player));
+        assert!(frame.get(sx - 2, sy) == theme::glyph(&Glyph::Goblin));
+    }
+
+    const LOG: &str = "
+The goblin hits you for 1 hit point.
+The goblin hits you for 1 hit point.
+You hit the goblin for 5 hit points.
+The goblin flees!
+";
+
+    #[test]
+    fn log() {
+        let game = played_game();
+        let (frame, _) = frame(|console| render_log(console, &game));
+        assert_eq!(text(&frame), LOG.trim_matches('\n'));
+
+        // each message in the colors of its age
+        for (n, (msg, age)) in game.log().latest(LOG_LINES).enumerate() {
+            let ch = msg.chars().next().unwrap();
+            let expected = Cell::new(ch, theme::log_message_fg(age), theme::log_message_bg(age));
+            assert!(
+                frame.get(LOG_OFFSET_X, LOG_OFFSET_Y + n) == expected,
+                "line {n}"
+            );
+        }
+    }
+
+    const HISTORY_BOX: &str = "
+      ┌────────────────────────┤ Message history ├───────────────────────┐
+      │ Welcome to the Dungeon!                                          │
+      │ The goblin hits you for 1 hit point.                             │
+      │ The goblin hits you for 1 hit point.                             │
+      │ The goblin hits you for 1 hit point.                             │
+      │ The goblin hits you for 1 hit point.                             │
+      │ You hit the goblin for 5 hit points.                             │
+      │ The goblin flees!                                                │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      │                                                                  │
+      └────────────────────────────────────────────────────────┤ Up/Dn ├─┘
+";
+
+    #[test]
+    fn history_box() {
+        let game = played_game();
+        let (frame, _) = frame(|console| render_history_box(console, &game, 0));
+        assert_eq!(text(&frame), HISTORY_BOX.trim_matches('\n'));
+    }
+}
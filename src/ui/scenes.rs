@@ -1,52 +1,448 @@
-use crate::console::{Color, Console, Event};
-use crate::logic::Game;
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use super::{CONSOLE_HEIGHT, Command, POPUP_MARGIN_V, Scene, Transition, input, render};
+use crate::console::{Color, Console, Event, Key, Mouse};
+use crate::logic::{Action, Game, Item};
 
-pub struct StartScreen;
+use super::camera::Camera;
+use super::{
+    Command, MIN_CONSOLE_HEIGHT, MIN_CONSOLE_WIDTH, Scene, Transition, input, keymap, render,
+};
+
+pub struct StartScreen {
+    save_file: Option<PathBuf>,
+    seed_input: Option<String>,
+    error: Option<String>,
+}
+
+impl StartScreen {
+    pub fn new(save_file: Option<&Path>) -> Self {
+        Self {
+            save_file: save_file
+                .filter(|path| path.exists())
+                .map(Path::to_path_buf),
+            seed_input: None,
+            error: None,
+        }
+    }
+
+    fn handle_seed_input(&mut self, game: &mut Game, event: Event) -> Transition {
+        let input = self.seed_input.as_mut().unwrap();
+        match event {
+            Event::KeyChar(ch) if ch.is_ascii_hexdigit() || ch == 'x' || ch == 'X' => {
+                if input.len() < 18 {
+                    input.push(ch);
+                    Transition::Okay
+                } else {
+                    Transition::Beep
+                }
+            }
+            Event::KeySpecial(Key::Backspace) => match input.pop() {
+                Some(_) => Transition::Okay,
+                None => Transition::Beep,
+            },
+            Event::KeySpecial(Key::Escape) => {
+                self.seed_input = None;
+                Transition::Okay
+            }
+            Event::KeySpecial(Key::Enter) => match crate::parse_seed(input).map(Game::new) {
+                Some(Ok(new_game)) => {
+                    *game = new_game;
+                    Transition::Switch(Box::new(PlayScreen::new()))
+                }
+                Some(Err(e)) => {
+                    self.error = Some(format!("Unplayable seed: {e}"));
+                    Transition::Beep
+                }
+                None => {
+                    self.error = Some(format!("Invalid seed: {input}"));
+                    Transition::Beep
+                }
+            },
+            _ => Transition::Beep,
+        }
+    }
+}
 
 impl Scene for StartScreen {
     fn render(&self, _game: &Game, console: &mut Console) {
-        let greeting = "Press any key to start...";
-        console.print(27, 21, greeting, Color::Default, Color::Default);
-        console.show_cursor(52, 21);
+        let mut lines = vec![
+            "Press any key to start a new game...",
+            "Press 's' to start from a chosen seed...",
+        ];
+        if self.save_file.is_some() {
+            lines.push("Press 'c' to continue the saved game...");
+        }
+        let y0 = console.height() / 2 - 1;
+        for (n, line) in lines.iter().enumerate() {
+            let x = (console.width() - line.len()) / 2;
+            console.print(x, y0 + n, line, Color::Default, Color::Default);
+        }
+        if let Some(error) = &self.error {
+            let x = console.width().saturating_sub(error.len()) / 2;
+            console.print(x, y0 + 5, error, Color::BrightRed, Color::Default);
+        }
+        if let Some(input) = &self.seed_input {
+            let prompt = format!("Seed: {input}");
+            let x = (console.width() - lines[0].len()) / 2;
+            console.print(x, y0 + 4, &prompt, Color::Default, Color::Default);
+            console.show_cursor(x + prompt.len(), y0 + 4);
+        } else {
+            let last = lines.last().unwrap();
+            let x = (console.width() - last.len()) / 2;
+            console.show_cursor(x + last.len(), y0 + lines.len() - 1);
+        }
+    }
+
+    fn handle_event(&mut self, game: &mut Game, event: Event) -> Transition {
+        if input::is_passive_mouse(&event) {
+            return Transition::Okay;
+        }
+        if self.seed_input.is_some() {
+            return self.handle_seed_input(game, event);
+        }
+        match (event, &self.save_file) {
+            (Event::KeyChar('s'), _) => {
+                self.seed_input = Some(String::new());
+                self.error = None;
+                Transition::Okay
+            }
+            (Event::KeyChar('c'), Some(path)) => match Game::load(path) {
+                Ok(saved) => {
+                    *game = saved;
+                    // a saved game can only be resumed once
+                    let _ = fs::remove_file(path);
+                    Transition::Switch(Box::new(PlayScreen::new()))
+                }
+                Err(e) => {
+                    self.error = Some(format!("Cannot load saved game: {e}"));
+                    Transition::Beep
+                }
+            },
+            _ => Transition::Switch(Box::new(PlayScreen::new())),
+        }
+    }
+}
+
+/// Shown instead of everything else while the console is below the minimum size.
+pub struct TooSmallScreen;
+
+impl TooSmallScreen {
+    pub fn fits(console: &Console) -> bool {
+        console.width() >= MIN_CONSOLE_WIDTH && console.height() >= MIN_CONSOLE_HEIGHT
+    }
+}
+
+impl Scene for TooSmallScreen {
+    fn render(&self, _game: &Game, console: &mut Console) {
+        let lines = [
+            "Terminal too small".to_string(),
+            format!("{}x{}", console.width(), console.height()),
+            format!("need {MIN_CONSOLE_WIDTH}x{MIN_CONSOLE_HEIGHT}"),
+        ];
+        for (n, line) in lines.iter().enumerate() {
+            let x = console.width().saturating_sub(line.len()) / 2;
+            let y = (console.height() / 2 + n).saturating_sub(1);
+            if y < console.height() {
+                console.print(x, y, line, Color::Default, Color::Default);
+            }
+        }
     }
 
     fn handle_event(&mut self, _game: &mut Game, _event: Event) -> Transition {
-        Transition::Switch(Box::new(PlayScreen))
+        Transition::Beep
     }
 }
 
-pub struct PlayScreen;
+pub struct PlayScreen {
+    /// Console position of the mouse pointer, if it has moved.
+    pointer: Option<(usize, usize)>,
+    /// Camera of the last frame, to find what the pointer is on.
+    camera: Cell<Option<Camera>>,
+}
+
+impl PlayScreen {
+    pub fn new() -> Self {
+        Self {
+            pointer: None,
+            camera: Cell::new(None),
+        }
+    }
+
+    fn handle_mouse(&mut self, game: &mut Game, mouse: Mouse, sx: usize, sy: usize) -> Transition {
+        self.pointer = Some((sx, sy));
+        if mouse != Mouse::Click {
+            return Transition::Okay;
+        }
+        let target = self.camera.get().and_then(|camera| camera.to_level(sx, sy));
+        match target {
+            Some(target) => travel(game, target),
+            None => Transition::Beep,
+        }
+    }
+}
 
 impl Scene for PlayScreen {
     fn render(&self, game: &Game, console: &mut Console) {
         render::render_map(console, game);
         render::render_log(console, game);
+        render::render_status(console, game);
+        let level = game.level();
+        let camera = Camera::new(console, level, level.player().unwrap().pos());
+        self.camera.set(Some(camera));
+        if let Some((sx, sy)) = self.pointer
+            && let Some(pos) = camera.to_level(sx, sy)
+            && let Some(text) = render::describe(game, pos)
+        {
+            render::render_tooltip(console, &text, (sx, sy));
+        }
     }
 
     fn handle_event(&mut self, game: &mut Game, event: Event) -> Transition {
+        if let Event::Mouse(mouse, sx, sy) = event {
+            return self.handle_mouse(game, mouse, sx, sy);
+        }
+        let Some(command) = input::map_play_command(event) else {
+            return Transition::Beep;
+        };
+        let action = match command {
+            Command::Move(dx, dy) => Action::Move(dx, dy),
+            Command::Descend => Action::Descend,
+            Command::Ascend => Action::Ascend,
+            Command::PickUp => Action::PickUp,
+            Command::Inventory => return Transition::Push(Box::new(InventoryPopup::new(false))),
+            Command::Drop => return Transition::Push(Box::new(InventoryPopup::new(true))),
+            Command::History => return Transition::Push(Box::new(HistoryPopup::new())),
+            Command::Help => return Transition::Push(Box::new(HelpPopup)),
+            Command::Look => {
+                let look = CursorScreen::new(game, "Look", Box::new(|_, _| Transition::Pop));
+                return Transition::Push(Box::new(look));
+            }
+            _ => unreachable!(),
+        };
+        match game.perform(action) {
+            Ok(_) if game.is_over() => Transition::Switch(Box::new(GameOverScreen)),
+            Ok(_) => Transition::Okay,
+            Err(_) => Transition::Beep,
+        }
+    }
+}
+
+/// Walk towards a clicked tile until something happens or a monster comes into view.
+fn travel(game: &mut Game, target: (usize, usize)) -> Transition {
+    let mut moved = false;
+    while let Some(path) = game.path_to(target.0, target.1)
+        && let Some(&(x, y)) = path.first()
+    {
+        let (px, py) = game.level().player().unwrap().pos();
+        let dx = (x as isize - px as isize) as i8;
+        let dy = (y as isize - py as isize) as i8;
+        if game.perform(Action::Move(dx, dy)).is_err() {
+            break;
+        }
+        moved = true;
+        if game.is_over() {
+            return Transition::Switch(Box::new(GameOverScreen));
+        }
+        let level = game.level();
+        let news = game.log().latest(1).any(|(_, age)| age == 0);
+        let danger = level
+            .actors()
+            .iter()
+            .any(|actor| level.is_visible(actor.x(), actor.y()));
+        if news || danger {
+            break;
+        }
+    }
+    if moved {
+        Transition::Okay
+    } else {
+        Transition::Beep
+    }
+}
+
+/// Decides what happens when a position is chosen with the cursor.
+type OnSelect = Box<dyn FnMut(&mut Game, (usize, usize)) -> Transition>;
+
+/// Moves a cursor over the map and describes what is under it, either to
+/// look around or to choose a target.
+pub struct CursorScreen {
+    prompt: &'static str,
+    /// Level position under the cursor, which drawing may bring back into view.
+    cursor: Cell<(usize, usize)>,
+    /// Camera of the last frame, which limits the cursor to what is on screen.
+    camera: Cell<Option<Camera>>,
+    on_select: OnSelect,
+}
+
+impl CursorScreen {
+    /// Start on the player; Enter or a click selects, Escape cancels.
+    pub fn new(game: &Game, prompt: &'static str, on_select: OnSelect) -> Self {
+        Self {
+            prompt,
+            cursor: Cell::new(game.level().player().unwrap().pos()),
+            camera: Cell::new(None),
+            on_select,
+        }
+    }
+
+    fn move_cursor(&mut self, dx: i8, dy: i8) -> Transition {
+        let (x, y) = self.cursor.get();
+        let next = x
+            .checked_add_signed(dx as isize)
+            .zip(y.checked_add_signed(dy as isize));
+        let camera = self.camera.get();
+        match (next, camera) {
+            // only onto the part of the level that is on screen
+            (Some((x, y)), Some(camera)) if camera.to_screen(x, y).is_some() => {
+                self.cursor.set((x, y));
+                Transition::Okay
+            }
+            _ => Transition::Beep,
+        }
+    }
+}
+
+impl Scene for CursorScreen {
+    fn render(&self, game: &Game, console: &mut Console) {
+        let level = game.level();
+        let player = level.player().unwrap().pos();
+        let camera = Camera::new(console, level, player);
+        self.camera.set(Some(camera));
+        let (x, y) = self.cursor.get();
+        // a console that shrank can leave the cursor out of view, but never
+        // the player
+        let (sx, sy) = camera.to_screen(x, y).unwrap_or_else(|| {
+            self.cursor.set(player);
+            camera.to_screen(player.0, player.1).unwrap()
+        });
+        let what = render::describe(game, self.cursor.get());
+        let what = what.as_deref().unwrap_or("unexplored");
+        render::render_prompt(console, &format!("{}: {what}", self.prompt));
+        console.show_cursor(sx, sy);
+    }
+
+    fn handle_event(&mut self, game: &mut Game, event: Event) -> Transition {
+        match event {
+            Event::KeySpecial(Key::Enter) => return (self.on_select)(game, self.cursor.get()),
+            Event::KeySpecial(Key::Escape) => return Transition::Pop,
+            Event::Mouse(mouse, sx, sy) => {
+                let Some(pos) = self.camera.get().and_then(|camera| camera.to_level(sx, sy)) else {
+                    return Transition::Okay;
+                };
+                self.cursor.set(pos);
+                return match mouse {
+                    Mouse::Click => (self.on_select)(game, pos),
+                    _ => Transition::Okay,
+                };
+            }
+            _ => {}
+        }
         match input::map_play_command(event) {
-            Some(command) => match command {
-                Command::Move(dx, dy) => match game.move_player(dx, dy) {
-                    Ok(_) => Transition::Okay,
+            Some(Command::Move(0, 0)) => Transition::Beep,
+            Some(Command::Move(dx, dy)) => self.move_cursor(dx, dy),
+            Some(Command::Look) => Transition::Pop,
+            _ => Transition::Beep,
+        }
+    }
+}
+
+pub struct GameOverScreen;
+
+impl Scene for GameOverScreen {
+    fn render(&self, game: &Game, console: &mut Console) {
+        render::render_map(console, game);
+        render::render_log(console, game);
+        render::render_status(console, game);
+        console.hide_cursor();
+        console.dim();
+    }
+
+    fn handle_event(&mut self, _game: &mut Game, event: Event) -> Transition {
+        match input::map_play_command(event) {
+            Some(Command::History) => Transition::Push(Box::new(HistoryPopup::new())),
+            _ if input::is_passive_mouse(&event) => Transition::Okay,
+            _ => Transition::Quit,
+        }
+    }
+}
+
+pub struct InventoryPopup {
+    dropping: bool,
+}
+
+impl InventoryPopup {
+    fn new(dropping: bool) -> Self {
+        Self { dropping }
+    }
+}
+
+impl Scene for InventoryPopup {
+    fn render(&self, game: &Game, console: &mut Console) {
+        console.hide_cursor();
+        console.dim();
+        let title = if self.dropping {
+            " Drop which item? "
+        } else {
+            " Use which item? "
+        };
+        render::render_inventory_box(console, game, title);
+    }
+
+    fn handle_event(&mut self, game: &mut Game, event: Event) -> Transition {
+        match input::map_select_command(event) {
+            Some(Command::Select(index)) => {
+                let targeted = game
+                    .inventory()
+                    .items()
+                    .get(index)
+                    .is_some_and(Item::needs_target);
+                if !self.dropping && targeted {
+                    let aim = CursorScreen::new(
+                        game,
+                        "Strike",
+                        Box::new(move |game, target| {
+                            match game.perform(Action::UseOn(index, target)) {
+                                Ok(_) if game.is_over() => {
+                                    Transition::Switch(Box::new(GameOverScreen))
+                                }
+                                Ok(_) => Transition::Pop,
+                                Err(_) => Transition::Beep,
+                            }
+                        }),
+                    );
+                    return Transition::Switch(Box::new(aim));
+                }
+                let action = if self.dropping {
+                    Action::Drop(index)
+                } else {
+                    Action::Use(index)
+                };
+                match game.perform(action) {
+                    Ok(_) if game.is_over() => Transition::Switch(Box::new(GameOverScreen)),
+                    Ok(_) => Transition::Pop,
                     Err(_) => Transition::Beep,
-                },
-                Command::History => Transition::Push(Box::new(HistoryPopup::new())),
-                _ => unreachable!(),
-            },
-            None => Transition::Beep,
+                }
+            }
+            _ if input::is_passive_mouse(&event) => Transition::Okay,
+            _ => Transition::Pop,
         }
     }
 }
 
 pub struct HistoryPopup {
     from_bottom: usize,
+    /// Number of lines shown in the last frame, which depends on the console size.
+    win_height: Cell<usize>,
 }
 
 impl HistoryPopup {
     fn new() -> Self {
-        Self { from_bottom: 0 }
+        Self {
+            from_bottom: 0,
+            win_height: Cell::new(0),
+        }
     }
 }
 
@@ -54,14 +450,15 @@ impl Scene for HistoryPopup {
     fn render(&self, game: &Game, console: &mut Console) {
         console.hide_cursor();
         console.dim();
-        let win_height = CONSOLE_HEIGHT - POPUP_MARGIN_V * 2 - 2;
+        let win_height = render::popup_lines(console);
+        self.win_height.set(win_height);
         let log_length = game.log().len();
         let scroll = log_length.saturating_sub(win_height + self.from_bottom);
         render::render_history_box(console, game, scroll);
     }
 
     fn handle_event(&mut self, game: &mut Game, event: Event) -> Transition {
-        let win_height = CONSOLE_HEIGHT - POPUP_MARGIN_V * 2 - 2;
+        let win_height = self.win_height.get();
         let log_length = game.log().len();
         let max_scroll = log_length.saturating_sub(win_height);
 
@@ -78,7 +475,96 @@ impl Scene for HistoryPopup {
                 self.from_bottom = next as usize;
                 Transition::Okay
             }
+            None if input::is_passive_mouse(&event) => Transition::Okay,
             None => Transition::Pop,
         }
     }
 }
+
+/// Lists the commands and their keys in the selected key map.
+pub struct HelpPopup;
+
+impl Scene for HelpPopup {
+    fn render(&self, _game: &Game, console: &mut Console) {
+        console.hide_cursor();
+        console.dim();
+        render::render_help_box(console, &keymap::describe());
+    }
+
+    fn handle_event(&mut self, _game: &mut Game, event: Event) -> Transition {
+        if input::is_passive_mouse(&event) {
+            Transition::Okay
+        } else {
+            Transition::Pop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::Memory;
+
+    fn console(width: usize, height: usize) -> Console {
+        Console::with_backend(Box::new(Memory::new(width, height, [])), "test").unwrap()
+    }
+
+    fn look(game: &Game) -> CursorScreen {
+        CursorScreen::new(game, "Look", Box::new(|_, _| Transition::Okay))
+    }
+
+    #[test]
+    fn cursor_stops_at_the_map_edge() {
+        let mut game = Game::new(3).unwrap();
+        let mut console = console(120, 50);
+        let mut screen = look(&game);
+        let right = Event::KeyChar('l');
+        screen.render(&game, &mut console);
+        while matches!(screen.handle_event(&mut game, right), Transition::Okay) {
+            screen.render(&game, &mut console);
+        }
+        let (x, _) = screen.cursor.get();
+        assert_eq!(x, game.level().width() - 1);
+        screen.render(&game, &mut console);
+        assert!(matches!(
+            screen.handle_event(&mut game, right),
+            Transition::Beep
+        ));
+    }
+
+    #[test]
+    fn cursor_comes_back_after_a_resize() {
+        let mut game = Game::new(3).unwrap();
+        game.take_stairs_down();
+        let player = game.level().player().unwrap().pos();
+        let mut console = console(240, 100);
+        let mut screen = look(&game);
+        // walk the cursor as far from the player as the screen allows
+        let (dx, dy) = (
+            if player.0 < game.level().width() / 2 {
+                1
+            } else {
+                -1
+            },
+            if player.1 < game.level().height() / 2 {
+                1
+            } else {
+                -1
+            },
+        );
+        screen.render(&game, &mut console);
+        while matches!(screen.move_cursor(dx, dy), Transition::Okay) {
+            screen.render(&game, &mut console);
+        }
+        assert_ne!(screen.cursor.get(), player);
+
+        console.resize(MIN_CONSOLE_WIDTH, MIN_CONSOLE_HEIGHT);
+        console.clear();
+        screen.render(&game, &mut console);
+        console.display().unwrap();
+        assert_eq!(screen.cursor.get(), player);
+        let camera = Camera::new(&console, game.level(), player);
+        let (_, cursor) = console.snapshot().unwrap();
+        assert_eq!(cursor, camera.to_screen(player.0, player.1));
+    }
+}
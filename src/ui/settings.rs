@@ -0,0 +1,171 @@
+//! Settings files of `key = value` lines, with built-in presets to start from.
+//!
+//! Themes and key maps are both read this way, and both are chosen once,
+//! before the game starts.
+
+use std::sync::OnceLock;
+use std::{fs, io};
+
+/// Something read from a preset or a file, one `key = value` line at a time.
+pub trait Settings: Sized {
+    /// What these settings are called in messages, such as "theme".
+    const KIND: &'static str;
+    /// Settings that do not need a file, by name.
+    const PRESETS: &'static [(&'static str, &'static str)];
+
+    /// Nothing set yet, for presets to be read on top of.
+    fn blank() -> Self;
+
+    /// Apply one line, other than `base`.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String>;
+
+    fn preset(name: &str) -> Option<Result<Self, String>> {
+        let (_, text) = Self::PRESETS.iter().find(|(preset, _)| *preset == name)?;
+        Some(Self::parse(text, Self::blank()))
+    }
+
+    /// Read `key = value` lines on top of `settings`.
+    ///
+    /// A `base = <preset>` line starts over from a preset.
+    fn parse(text: &str, mut settings: Self) -> Result<Self, String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) if key.trim() == "base" => match Self::preset(value.trim()) {
+                    Some(preset) => preset.map(|preset| settings = preset),
+                    None => Err(format!("unknown {} '{}'", Self::KIND, value.trim())),
+                },
+                Some((key, value)) => settings.set(key.trim(), value.trim()),
+                None => Err("expected 'key = value'".to_string()),
+            };
+            result.map_err(|e| format!("line {}: {e}", n + 1))?;
+        }
+        Ok(settings)
+    }
+}
+
+/// Use a preset, or a file read on top of `default`, from now on.
+pub fn select<S: Settings>(
+    chosen: &OnceLock<S>,
+    name_or_path: &str,
+    default: fn() -> S,
+) -> Result<(), io::Error> {
+    let settings = match S::preset(name_or_path) {
+        Some(preset) => preset,
+        // keys missing from the file keep their default values
+        None => S::parse(&fs::read_to_string(name_or_path)?, default()),
+    };
+    let settings = settings.map_err(|e| {
+        let msg = format!("invalid {} {name_or_path}: {e}", S::KIND);
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    })?;
+    install(chosen, settings, name_or_path)
+}
+
+/// Use `settings` from now on, unless others are already in use.
+pub fn install<S: Settings>(
+    chosen: &OnceLock<S>,
+    settings: S,
+    name: &str,
+) -> Result<(), io::Error> {
+    chosen.set(settings).map_err(|_| {
+        let msg = format!(
+            "cannot use {} {name}, another one is already in use",
+            S::KIND
+        );
+        io::Error::new(io::ErrorKind::AlreadyExists, msg)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings that only remember what was set, in order.
+    struct Lines(Vec<String>);
+
+    impl Settings for Lines {
+        const KIND: &'static str = "list";
+        const PRESETS: &'static [(&'static str, &'static str)] = &[
+            ("short", "a = 1"),
+            ("long", "base = short\nb = 2"),
+            ("broken", "a"),
+        ];
+
+        fn blank() -> Self {
+            Lines(vec![])
+        }
+
+        fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+            if key == "bad" {
+                return Err(format!("bad value '{value}'"));
+            }
+            self.0.push(format!("{key}={value}"));
+            Ok(())
+        }
+    }
+
+    fn parse(text: &str) -> Result<Vec<String>, String> {
+        Lines::parse(text, Lines(vec!["x=0".to_string()])).map(|lines| lines.0)
+    }
+
+    #[test]
+    fn lines_are_applied_in_order() {
+        assert_eq!(
+            parse("# comment\n\n  b =  2 \na=1").unwrap(),
+            ["x=0", "b=2", "a=1"]
+        );
+    }
+
+    #[test]
+    fn base_starts_over_from_a_preset() {
+        assert_eq!(
+            parse("c = 3\nbase = long\nd = 4").unwrap(),
+            ["a=1", "b=2", "d=4"]
+        );
+    }
+
+    #[test]
+    fn errors_tell_the_line() {
+        assert_eq!(
+            parse("a = 1\n\nbad = 7").err().unwrap(),
+            "line 3: bad value '7'"
+        );
+        assert_eq!(
+            parse("# a = 1\na").err().unwrap(),
+            "line 2: expected 'key = value'"
+        );
+        assert_eq!(
+            parse("base = tall").err().unwrap(),
+            "line 1: unknown list 'tall'"
+        );
+        assert_eq!(
+            parse("base = broken").err().unwrap(),
+            "line 1: line 1: expected 'key = value'"
+        );
+    }
+
+    #[test]
+    fn second_selection_is_reported() {
+        let chosen = OnceLock::new();
+        select(&chosen, "short", Lines::blank).unwrap();
+        let e = select(&chosen, "long", Lines::blank).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(chosen.get().unwrap().0, ["a=1"]);
+    }
+
+    #[test]
+    fn files_are_read_on_top_of_the_default() {
+        let path = crate::testing::temp_path("settings.list");
+        fs::write(&path, "b = 2").unwrap();
+        let chosen = OnceLock::new();
+        select(&chosen, path.to_str().unwrap(), || {
+            Lines::preset("short").unwrap().unwrap()
+        })
+        .unwrap();
+        assert_eq!(chosen.get().unwrap().0, ["a=1", "b=2"]);
+    }
+}
@@ -1,42 +1,300 @@
+use std::io;
+use std::sync::OnceLock;
+
 use crate::{
     console::{Cell, Color},
-    logic::{Glyph, Tile},
+    logic::{FULL_LIGHT, Glyph, Item, Tile},
 };
 
+use super::settings::{self, Settings};
+
+/// Themes that do not need a file, by name.
+const PRESETS: [(&str, &str); 3] = [
+    ("classic", include_str!("../../themes/classic.theme")),
+    ("ascii", include_str!("../../themes/ascii.theme")),
+    ("dusk", include_str!("../../themes/dusk.theme")),
+];
+
+const TILES: [&str; 11] = [
+    "wall",
+    "floor",
+    "down-stairs",
+    "up-stairs",
+    "torch",
+    "open-door",
+    "closed-door",
+    "locked-door",
+    "water",
+    "lava",
+    "chasm",
+];
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Glyphs and colors of everything drawn on the console.
+struct Theme {
+    player: Cell,
+    goblin: Cell,
+    hobgoblin: Cell,
+    healing_potion: Cell,
+    lightning_scroll: Cell,
+    mapping_scroll: Cell,
+    oil_flask: Cell,
+    visible: [Cell; TILES.len()],
+    dim: [Cell; TILES.len()],
+    explored: [Cell; TILES.len()],
+    message_fg: Vec<Color>,
+    message_bg: Color,
+    status_fg: Color,
+    status_danger: Color,
+    status_bg: Color,
+    seed_fg: Color,
+    box_chars: [char; 8],
+    box_fg: Color,
+    box_bg: Color,
+    history_fg: Color,
+    history_bg: Color,
+    tooltip_fg: Color,
+    tooltip_bg: Color,
+}
+
+impl Theme {
+    fn classic() -> Self {
+        Self::preset("classic").unwrap().unwrap()
+    }
+}
+
+impl Settings for Theme {
+    const KIND: &'static str = "theme";
+    const PRESETS: &'static [(&'static str, &'static str)] = &PRESETS;
+
+    fn blank() -> Self {
+        Self {
+            player: Cell::default(),
+            goblin: Cell::default(),
+            hobgoblin: Cell::default(),
+            healing_potion: Cell::default(),
+            lightning_scroll: Cell::default(),
+            mapping_scroll: Cell::default(),
+            oil_flask: Cell::default(),
+            visible: [Cell::default(); TILES.len()],
+            dim: [Cell::default(); TILES.len()],
+            explored: [Cell::default(); TILES.len()],
+            message_fg: vec![Color::Default],
+            message_bg: Color::Default,
+            status_fg: Color::Default,
+            status_danger: Color::Default,
+            status_bg: Color::Default,
+            seed_fg: Color::Default,
+            box_chars: [' '; 8],
+            box_fg: Color::Default,
+            box_bg: Color::Default,
+            history_fg: Color::Default,
+            history_bg: Color::Default,
+            tooltip_fg: Color::Default,
+            tooltip_bg: Color::Default,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if let Some(index) = TILES.iter().position(|tile| *tile == key) {
+            self.visible[index] = parse_cell(value)?;
+            return Ok(());
+        }
+        if let Some(tile) = key.strip_suffix(".dim")
+            && let Some(index) = TILES.iter().position(|name| *name == tile)
+        {
+            self.dim[index] = parse_cell(value)?;
+            return Ok(());
+        }
+        if let Some(tile) = key.strip_suffix(".explored")
+            && let Some(index) = TILES.iter().position(|name| *name == tile)
+        {
+            self.explored[index] = parse_cell(value)?;
+            return Ok(());
+        }
+        match key {
+            "player" => self.player = parse_cell(value)?,
+            "goblin" => self.goblin = parse_cell(value)?,
+            "hobgoblin" => self.hobgoblin = parse_cell(value)?,
+            "healing-potion" => self.healing_potion = parse_cell(value)?,
+            "lightning-scroll" => self.lightning_scroll = parse_cell(value)?,
+            "mapping-scroll" => self.mapping_scroll = parse_cell(value)?,
+            "oil-flask" => self.oil_flask = parse_cell(value)?,
+            "message.fg" => {
+                let colors = value
+                    .split_whitespace()
+                    .map(parse_color)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if colors.is_empty() {
+                    return Err("expected at least one color".to_string());
+                }
+                self.message_fg = colors;
+            }
+            "message.bg" => self.message_bg = parse_color(value)?,
+            "status.fg" => self.status_fg = parse_color(value)?,
+            "status.danger" => self.status_danger = parse_color(value)?,
+            "status.bg" => self.status_bg = parse_color(value)?,
+            "seed.fg" => self.seed_fg = parse_color(value)?,
+            "box" => {
+                let chars: Vec<char> = value.chars().collect();
+                self.box_chars = chars
+                    .try_into()
+                    .map_err(|_| "expected 8 box characters".to_string())?;
+            }
+            "box.fg" => self.box_fg = parse_color(value)?,
+            "box.bg" => self.box_bg = parse_color(value)?,
+            "history.fg" => self.history_fg = parse_color(value)?,
+            "history.bg" => self.history_bg = parse_color(value)?,
+            "tooltip.fg" => self.tooltip_fg = parse_color(value)?,
+            "tooltip.bg" => self.tooltip_bg = parse_color(value)?,
+            _ => return Err(format!("unknown key '{key}'")),
+        }
+        Ok(())
+    }
+}
+
+/// A character, a foreground color and an optional background color.
+fn parse_cell(value: &str) -> Result<Cell, String> {
+    let mut parts = value.split_whitespace();
+    let mut chars = parts.next().unwrap_or_default().chars();
+    let (Some(ch), None) = (chars.next(), chars.next()) else {
+        return Err(format!("expected a single character in '{value}'"));
+    };
+    let fg = parse_color(parts.next().unwrap_or("default"))?;
+    let bg = parse_color(parts.next().unwrap_or("default"))?;
+    if parts.next().is_some() {
+        return Err(format!("too many values in '{value}'"));
+    }
+    Ok(Cell::new(ch, fg, bg))
+}
+
+fn parse_color(name: &str) -> Result<Color, String> {
+    let color = match name {
+        "default" => Color::Default,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "bright-black" => Color::BrightBlack,
+        "bright-red" => Color::BrightRed,
+        "bright-green" => Color::BrightGreen,
+        "bright-yellow" => Color::BrightYellow,
+        "bright-blue" => Color::BrightBlue,
+        "bright-magenta" => Color::BrightMagenta,
+        "bright-cyan" => Color::BrightCyan,
+        "bright-white" => Color::BrightWhite,
+        _ => {
+            let parsed = match name.strip_prefix('#') {
+                Some(hex) if hex.len() == 6 => u32::from_str_radix(hex, 16)
+                    .ok()
+                    .map(|rgb| Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+                Some(_) => None,
+                None => name.parse().ok().map(Color::Indexed),
+            };
+            parsed.ok_or_else(|| format!("unknown color '{name}'"))?
+        }
+    };
+    Ok(color)
+}
+
+/// Use a built-in theme or load one from a file, before anything is drawn.
+pub fn select(name_or_path: &str) -> Result<(), io::Error> {
+    settings::select(&THEME, name_or_path, Theme::classic)
+}
+
+fn current() -> &'static Theme {
+    THEME.get_or_init(Theme::classic)
+}
+
+fn tile_index(tile: &Tile) -> usize {
+    match tile {
+        Tile::Wall => 0,
+        Tile::Floor => 1,
+        Tile::DownStairs => 2,
+        Tile::UpStairs => 3,
+        Tile::Torch => 4,
+        Tile::DoorOpen => 5,
+        Tile::DoorClosed => 6,
+        Tile::DoorLocked => 7,
+        Tile::Water => 8,
+        Tile::Lava => 9,
+        Tile::Chasm => 10,
+    }
+}
+
 pub fn glyph(glyph: &Glyph) -> Cell {
+    let theme = current();
     match glyph {
-        Glyph::Player => Cell::new('@', Color::BrightWhite, Color::Black),
-        Glyph::Goblin => Cell::new('g', Color::BrightRed, Color::Black),
-        Glyph::Hobgobin => Cell::new('H', Color::BrightRed, Color::Black),
+        Glyph::Player => theme.player,
+        Glyph::Goblin => theme.goblin,
+        Glyph::Hobgobin => theme.hobgoblin,
     }
 }
 
-pub fn visible_tile(tile: &Tile) -> Cell {
-    match tile {
-        Tile::Wall => Cell::new('#', Color::BrightWhite, Color::Black),
-        Tile::Floor => Cell::new('.', Color::BrightWhite, Color::Black),
+pub fn item(item: &Item) -> Cell {
+    let theme = current();
+    match item {
+        Item::HealingPotion => theme.healing_potion,
+        Item::LightningScroll => theme.lightning_scroll,
+        Item::MappingScroll => theme.mapping_scroll,
+        Item::OilFlask => theme.oil_flask,
     }
 }
 
-pub fn explored_tile(tile: &Tile) -> Cell {
-    match tile {
-        Tile::Wall => Cell::new('#', Color::BrightBlack, Color::Black),
-        Tile::Floor => Cell::new('.', Color::BrightBlack, Color::Black),
+/// A tile in view, shaded by how brightly it is lit.
+///
+/// Tiles in full light use their plain cell and the others their `.dim`
+/// cell, unless both have RGB colors and the shades in between can be mixed.
+pub fn lit_tile(tile: &Tile, light: u8) -> Cell {
+    let theme = current();
+    let index = tile_index(tile);
+    if light >= FULL_LIGHT {
+        return theme.visible[index];
     }
+    let amount = light as u32 * 255 / FULL_LIGHT as u32;
+    theme.dim[index].shade_towards(theme.visible[index], amount as u8)
+}
+
+pub fn explored_tile(tile: &Tile) -> Cell {
+    current().explored[tile_index(tile)]
 }
 
 pub fn log_message_fg(age: u64) -> Color {
-    match age {
-        0 => Color::BrightWhite,
-        1 => Color::White,
-        _ => Color::BrightBlack,
-    }
+    let fading = &current().message_fg;
+    fading[(age as usize).min(fading.len() - 1)]
 }
 
 pub fn log_message_bg(_age: u64) -> Color {
-    Color::Black
+    current().message_bg
+}
+
+pub fn status_fg(hp: i32, max_hp: i32) -> Color {
+    if hp * 4 <= max_hp {
+        current().status_danger
+    } else {
+        current().status_fg
+    }
+}
+
+pub fn prompt_fg() -> Color {
+    current().status_fg
+}
+
+pub fn seed_fg() -> Color {
+    current().seed_fg
+}
+
+pub fn status_bg() -> Color {
+    current().status_bg
 }
 
+/// Parts of a box, in the order of the `box` theme key.
+#[derive(Clone, Copy)]
 pub enum Decoration {
     TopLeftCorner,
     TopRightCorner,
@@ -49,30 +307,90 @@ pub enum Decoration {
 }
 
 pub fn box_decoration(which: Decoration) -> Cell {
-    match which {
-        Decoration::TopLeftCorner => Cell::new('┌', box_fg(), box_bg()),
-        Decoration::TopRightCorner => Cell::new('┐', box_fg(), box_bg()),
-        Decoration::BottomLeftCorner => Cell::new('└', box_fg(), box_bg()),
-        Decoration::BottomRightCorner => Cell::new('┘', box_fg(), box_bg()),
-        Decoration::Horizontal => Cell::new('─', box_fg(), box_bg()),
-        Decoration::Vertical => Cell::new('│', box_fg(), box_bg()),
-        Decoration::LeftBracket => Cell::new('┤', box_fg(), box_bg()),
-        Decoration::RightBracket => Cell::new('├', box_fg(), box_bg()),
-    }
+    Cell::new(current().box_chars[which as usize], box_fg(), box_bg())
 }
 
 pub fn box_fg() -> Color {
-    Color::BrightWhite
+    current().box_fg
 }
 
 pub fn box_bg() -> Color {
-    Color::Black
+    current().box_bg
 }
 
 pub fn history_fg() -> Color {
-    Color::White
+    current().history_fg
 }
 
 pub fn history_bg() -> Color {
-    Color::Black
+    current().history_bg
+}
+
+pub fn tooltip_fg() -> Color {
+    current().tooltip_fg
+}
+
+pub fn tooltip_bg() -> Color {
+    current().tooltip_bg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Theme, String> {
+        Theme::parse(text, Theme::blank())
+    }
+
+    #[test]
+    fn presets_parse() {
+        for (name, _) in PRESETS {
+            assert!(Theme::preset(name).unwrap().is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn tile_keys_and_shades() {
+        let theme = parse("wall = # #a89f91\nwall.dim = # red\nwall.explored = # 52").unwrap();
+        let index = tile_index(&Tile::Wall);
+        assert!(
+            theme.visible[index] == Cell::new('#', Color::Rgb(0xa8, 0x9f, 0x91), Color::Default)
+        );
+        assert!(theme.dim[index] == Cell::new('#', Color::Red, Color::Default));
+        assert!(theme.explored[index] == Cell::new('#', Color::Indexed(52), Color::Default));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert_eq!(
+            parse("# comment\n\ngremlin = g red").err().unwrap(),
+            "line 3: unknown key 'gremlin'"
+        );
+        assert!(parse("wall.shiny = # red").is_err());
+        assert!(parse("goblin g red").is_err());
+        assert_eq!(
+            parse("base = neon").err().unwrap(),
+            "line 1: unknown theme 'neon'"
+        );
+    }
+
+    #[test]
+    fn second_selection_is_reported() {
+        // the default theme, so that rendering tests running alongside are not affected
+        let _ = select("classic");
+        let e = select("classic").err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn bad_colors_are_rejected() {
+        for value in ["g chartreuse", "g #12345", "g #12345g", "g 256", "g red -1"] {
+            let e = parse(&format!("goblin = {value}")).err().unwrap();
+            assert!(e.starts_with("line 1: unknown color"), "{value}: {e}");
+        }
+        assert!(parse("goblin = gg red").is_err());
+        assert!(parse("goblin = g red black blue").is_err());
+        assert!(parse("message.fg =").is_err());
+        assert!(parse("status.bg = purple").is_err());
+    }
 }